@@ -0,0 +1,459 @@
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::path::PathBuf;
+
+use ggez::{GameError, GameResult};
+use serde_json;
+
+use marker::{self, MarkedFormat, MaskMode, SaveOptions, SpriteData};
+use sprite::{variants, Loader};
+use sprite::texture_packer::SpriteSheetInfo;
+use export::{godot, ldtk, texture_packer};
+use lint::{self, LintConfig, Rule, Severity};
+use coverage::{self, BlobMode};
+use contact_sheet::{self, ContactSheetOptions};
+use diff;
+
+const USAGE: &'static str = "usage:
+    marker [sheet.json [atlas.png]] [--width px] [--height px] [--marked marked.json]
+    marker ldtk <sheet.json> [marked.json] [-o out.json]
+    marker godot <sheet.json> [marked.json] [-o out.tres] [--res-path res://path.png]
+    marker convert <marked.(json|ron|bin)> <out.(json|ron|bin)> [--ground squares|4|8]
+    marker codegen <marked.json> [-o sprites.rs]
+    marker lint <marked.json> [sheet.json] [--deny-warnings]
+                [--allow rule] [--warn rule] [--deny rule]
+    marker coverage <marked.json> [--mode 47|256] [--format text|json] [--png missing.png]
+    marker render <marked.json> <atlas.png> -o <out.png> [--columns n] [--cell px]
+                  [--sheet sheet.json]
+    marker diff <a.json> <b.json>
+    marker merge <base.json> <ours.json> <theirs.json> [-o out.json]
+    marker pivots <sheet.json> [marked.json] [-o out.json]
+    marker animations <marked.json> [--auto] [-o out.json]";
+
+/// Options that don't take a value.
+const FLAGS: &'static [&'static str] = &["--deny-warnings", "--auto"];
+
+/// Runs a subcommand if the arguments name one, returning the exit code.
+/// `None` means the GUI should start instead.
+pub fn run(args: &[String]) -> Option<i32> {
+    let (command, rest) = match args.split_first() {
+        Some(split) => split,
+        None => return None,
+    };
+
+    let args = Args::parse(rest);
+    let result = match command.as_str() {
+        "ldtk" => export_ldtk(&args).map(|_| 0),
+        "godot" => export_godot(&args).map(|_| 0),
+        "convert" => convert(&args).map(|_| 0),
+        "codegen" => codegen(&args).map(|_| 0),
+        "lint" => lint(&args),
+        "coverage" => coverage(&args).map(|_| 0),
+        "render" => render(&args).map(|_| 0),
+        "diff" => diff(&args),
+        "merge" => merge(&args),
+        "pivots" => export_pivots(&args).map(|_| 0),
+        "animations" => export_animations(&args).map(|_| 0),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(0)
+        }
+        _ => return None,
+    };
+
+    match result {
+        Ok(code) => Some(code),
+        Err(e) => {
+            eprintln!("{}", e);
+            Some(2)
+        }
+    }
+}
+
+/// What the editor window starts with.
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    /// Meta and atlas image, `None` shows a file picker.
+    pub sheet: Option<(PathBuf, PathBuf)>,
+    /// Overrides the marked file found next to the meta.
+    pub marked: Option<PathBuf>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Parses the GUI arguments, used when `run` didn't find a subcommand.
+/// The atlas defaults to the meta's `image`, relative to the meta file.
+pub fn open_options(args: &[String]) -> GameResult<OpenOptions> {
+    let args = Args::parse(args);
+
+    let sheet = match args.get(0) {
+        Some(meta) => {
+            let meta = PathBuf::from(meta);
+            let image = match args.get(1) {
+                Some(image) => PathBuf::from(image),
+                None => Loader::resolve_image(&meta, &SpriteSheetInfo::load_info(&meta)?)?,
+            };
+            Some((meta, image))
+        }
+        None => None,
+    };
+
+    Ok(OpenOptions {
+        sheet,
+        marked: args.option(&["-m", "--marked"]).map(PathBuf::from),
+        width: args.number(&["--width"])?.unwrap_or(1600),
+        height: args.number(&["--height"])?.unwrap_or(1000),
+    })
+}
+
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Args {
+        let mut positional = vec![];
+        let mut options = vec![];
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            if FLAGS.contains(&arg.as_str()) {
+                options.push((arg.clone(), String::new()));
+            } else if arg.starts_with('-') {
+                let value = iter.next().cloned().unwrap_or_default();
+                options.push((arg.clone(), value));
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+
+        Args {
+            positional,
+            options,
+        }
+    }
+
+    fn get(&self, ix: usize) -> Option<&String> {
+        self.positional.get(ix)
+    }
+
+    fn require(&self, ix: usize, what: &str) -> GameResult<&String> {
+        self.get(ix)
+            .ok_or_else(|| GameError::from(format!("missing {}\n{}", what, USAGE)))
+    }
+
+    fn option(&self, names: &[&str]) -> Option<&String> {
+        self.options
+            .iter()
+            .find(|&&(ref name, _)| names.contains(&name.as_str()))
+            .map(|&(_, ref value)| value)
+    }
+
+    fn number<T: ::std::str::FromStr>(&self, names: &[&str]) -> GameResult<Option<T>> {
+        match self.option(names) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| GameError::from(format!("{} is not a number", value))),
+            None => Ok(None),
+        }
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.option(&[name]).is_some()
+    }
+}
+
+/// Every page of the sheet and the marked data of all of them.
+fn load_sheet(args: &Args) -> GameResult<(Vec<SpriteSheetInfo>, Vec<SpriteData>)> {
+    let meta = PathBuf::from(args.require(0, "sheet json")?);
+    let marked = args.get(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| ::find_marked_path(&meta));
+
+    let pages = Loader::load_pages(&meta)?;
+    let marked = SpriteData::load_marked(&marked)?;
+    Ok((pages, marked))
+}
+
+fn write_output(args: &Args, contents: &[u8]) -> GameResult<()> {
+    match args.option(&["-o", "--output"]) {
+        Some(path) => File::create(path)?.write_all(contents)?,
+        None => io::stdout().write_all(contents)?,
+    };
+    Ok(())
+}
+
+fn identifier(image: &str) -> String {
+    let stem = image.split('.').next().unwrap_or(image);
+    let mut chars = stem.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' });
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn export_ldtk(args: &Args) -> GameResult<()> {
+    let (pages, marked) = load_sheet(args)?;
+    let defs = ldtk::project_defs(&pages, &marked, &identifier(&pages[0].meta.image));
+    let json = serde_json::to_string_pretty(&defs)
+        .map_err(|e| GameError::from(format!("{}", e)))?;
+    write_output(args, json.as_bytes())
+}
+
+/// `--res-path` is the Godot path of the first page's atlas, the atlases
+/// of the other pages are expected next to it.
+fn export_godot(args: &Args) -> GameResult<()> {
+    let (pages, marked) = load_sheet(args)?;
+    let res_paths: Vec<String> = match args.option(&["--res-path"]) {
+        Some(first) => {
+            let dir = &first[..first.rfind('/').map(|ix| ix + 1).unwrap_or(0)];
+            pages
+                .iter()
+                .enumerate()
+                .map(|(page, info)| match page {
+                    0 => first.clone(),
+                    _ => format!("{}{}", dir, info.meta.image),
+                })
+                .collect()
+        }
+        None => pages.iter().map(|info| format!("res://{}", info.meta.image)).collect(),
+    };
+    let metas: Vec<_> = pages.into_iter().map(|info| info.meta).collect();
+    let tres = godot::tileset(&marked, &metas, &res_paths);
+    write_output(args, tres.as_bytes())
+}
+
+/// Writes the marked pivots into the TexturePacker JSON. Without `-o` every
+/// page and scale variant of the sheet is updated in place.
+fn export_pivots(args: &Args) -> GameResult<()> {
+    let meta = PathBuf::from(args.require(0, "sheet json")?);
+    let marked = args.get(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| ::find_marked_path(&meta));
+    let marked = SpriteData::load_marked(&marked)?;
+
+    let output = args.option(&["-o", "--output"]);
+    let sheets = match output {
+        Some(_) => vec![meta],
+        None => {
+            let mut sheets = vec![];
+            for variant in variants::scale_variants(&meta) {
+                sheets.extend(Loader::page_paths(&variant)?);
+            }
+            sheets
+        }
+    };
+
+    for sheet in sheets.iter() {
+        let file = BufReader::new(File::open(sheet)?);
+        let mut json: serde_json::Value =
+            serde_json::from_reader(file).map_err(|e| GameError::from(format!("{}", e)))?;
+        let changed = texture_packer::apply_pivots(&mut json, &marked);
+        let contents = serde_json::to_string_pretty(&json)
+            .map_err(|e| GameError::from(format!("{}", e)))?;
+
+        let target = output.map(PathBuf::from).unwrap_or_else(|| sheet.clone());
+        let backups = SaveOptions::default().backups;
+        marker::save_file(&target, contents.as_bytes(), backups)?;
+        eprintln!("{}: {} pivot(s) updated", target.display(), changed);
+    }
+    Ok(())
+}
+
+/// Prints the sheet's animations as JSON, with `--auto` adding one for every
+/// numbered frame sequence that doesn't have an animation yet.
+fn export_animations(args: &Args) -> GameResult<()> {
+    let marked_path = args.require(0, "marked file")?;
+    let marked = SpriteData::load_marked(marked_path)?;
+    let path = marker::animations_path(marked_path);
+    let mut animations = if path.exists() {
+        marker::load_animations(&path)?
+    } else {
+        vec![]
+    };
+
+    if args.flag("--auto") {
+        let added = marker::add_missing(&mut animations, marker::auto_group(&marked));
+        eprintln!("{} animation(s) grouped from frame names", added);
+    }
+
+    let json = serde_json::to_string_pretty(&animations)
+        .map_err(|e| GameError::from(format!("{}", e)))?;
+    write_output(args, json.as_bytes())
+}
+
+fn convert(args: &Args) -> GameResult<()> {
+    let input = args.require(0, "input marked file")?;
+    let output = args.require(1, "output marked file")?;
+    let format = MarkedFormat::from_path(output)
+        .ok_or_else(|| GameError::from(format!("unknown format of {}", output)))?;
+
+    let mut marked = SpriteData::load_marked(input)?;
+    if let Some(ground) = args.option(&["--ground"]) {
+        let mode = match ground.as_str() {
+            "squares" => None,
+            "4" => Some(MaskMode::Sides),
+            "8" => Some(MaskMode::Blob),
+            other => return Err(GameError::from(format!("unknown ground {}", other))),
+        };
+        for name in marker::mask::convert_ground(&mut marked, mode) {
+            eprintln!("{}: squares can't express the mask, kept it", name);
+        }
+    }
+
+    let options = SaveOptions {
+        format: Some(format),
+        ..Default::default()
+    };
+    marker::save_marked(output, &marked, &options)
+}
+
+fn codegen(args: &Args) -> GameResult<()> {
+    let marked = SpriteData::load_marked(args.require(0, "marked file")?)?;
+    write_output(args, ::codegen::generate(&marked).as_bytes())
+}
+
+fn lint(args: &Args) -> GameResult<i32> {
+    let marked = SpriteData::load_marked(args.require(0, "marked file")?)?;
+    let info = match args.get(1) {
+        Some(meta) => Some(SpriteSheetInfo::merge(&Loader::load_pages(meta)?)),
+        None => None,
+    };
+
+    let mut config = LintConfig::default();
+    for &(ref option, ref value) in args.options.iter() {
+        let level = match option.as_str() {
+            "--allow" => None,
+            "--warn" => Some(Severity::Warning),
+            "--deny" => Some(Severity::Error),
+            _ => continue,
+        };
+        let rule = Rule::from_name(value)
+            .ok_or_else(|| GameError::from(format!("unknown lint rule {}", value)))?;
+        config.set(rule, level);
+    }
+
+    let mut problems = lint::lint(&marked, info.as_ref(), &config);
+    let animations = marker::animations_path(args.require(0, "marked file")?);
+    if animations.exists() {
+        let animations = marker::load_animations(animations)?;
+        problems.extend(lint::animations(&animations, &marked, &config));
+    }
+    if let Some(meta) = args.get(1) {
+        problems.extend(lint::variants(&variants::load_variants(meta)?, &config));
+    }
+    problems.sort_by(|a, b| b.severity.cmp(&a.severity));
+    for p in problems.iter() {
+        println!("{}", p);
+    }
+
+    let errors = problems.iter().filter(|p| p.severity == Severity::Error).count();
+    let warnings = problems.len() - errors;
+    eprintln!("{} error(s), {} warning(s)", errors, warnings);
+
+    if errors > 0 || (warnings > 0 && args.flag("--deny-warnings")) {
+        Ok(1)
+    } else {
+        Ok(0)
+    }
+}
+
+fn coverage(args: &Args) -> GameResult<()> {
+    let marked = SpriteData::load_marked(args.require(0, "marked file")?)?;
+    let mode = match args.option(&["--mode"]).map(|m| m.as_str()) {
+        None | Some("47") => BlobMode::Blob47,
+        Some("256") => BlobMode::Full256,
+        Some(other) => return Err(GameError::from(format!("unknown mode {}", other))),
+    };
+
+    let report = coverage::analyse(&marked, mode);
+
+    if let Some(png) = args.option(&["--png"]) {
+        report
+            .render_missing()
+            .save(png)
+            .map_err(|e| GameError::from(format!("{}", e)))?;
+    }
+
+    match args.option(&["--format"]).map(|f| f.as_str()) {
+        None | Some("text") => write_output(args, report.to_text().as_bytes()),
+        Some("json") => {
+            let json = serde_json::to_string_pretty(&report)
+                .map_err(|e| GameError::from(format!("{}", e)))?;
+            write_output(args, json.as_bytes())
+        }
+        Some(other) => Err(GameError::from(format!("unknown format {}", other))),
+    }
+}
+
+/// With `--sheet` the atlases of the sheet's other pages are loaded too,
+/// `atlas.png` is the atlas of the given page.
+fn render(args: &Args) -> GameResult<()> {
+    let marked = SpriteData::load_marked(args.require(0, "marked file")?)?;
+    let atlas = args.require(1, "atlas image")?;
+    let output = args.option(&["-o", "--output"])
+        .ok_or_else(|| GameError::from(format!("missing output png\n{}", USAGE)))?;
+
+    let mut opts = ContactSheetOptions::default();
+    if let Some(columns) = args.number(&["--columns"])? {
+        opts.columns = columns;
+    }
+    if let Some(cell) = args.number(&["--cell"])? {
+        opts.cell = cell;
+    }
+    // The atlas of an @2x sheet has twice the pixels of the marked frames.
+    let atlases = match args.option(&["--sheet"]) {
+        Some(sheet) => {
+            opts.atlas_scale = SpriteSheetInfo::load_info(sheet)?.meta.scale_factor();
+            Loader::load_page_images(sheet, atlas)?
+        }
+        None => vec![Loader::load_rgba(atlas)?],
+    };
+    if let Some(sd) = marked.iter().find(|sd| sd.page >= atlases.len()) {
+        return Err(GameError::from(format!(
+            "{} is on page {} of a multi-pack sheet, pass the sheet with --sheet",
+            sd.name, sd.page
+        )));
+    }
+
+    contact_sheet::render(&marked, &atlases, &opts)
+        .save(output)
+        .map_err(|e| GameError::from(format!("{}", e)))
+}
+
+fn diff(args: &Args) -> GameResult<i32> {
+    let a = SpriteData::load_marked(args.require(0, "first marked file")?)?;
+    let b = SpriteData::load_marked(args.require(1, "second marked file")?)?;
+
+    let changes = diff::diff(&a, &b);
+    for change in changes.iter() {
+        println!("{}", change);
+    }
+    Ok(if changes.is_empty() { 0 } else { 1 })
+}
+
+/// Writes the merge result over `ours` unless told otherwise, which is what
+/// git expects from a merge driver.
+fn merge(args: &Args) -> GameResult<i32> {
+    let base = SpriteData::load_marked(args.require(0, "base marked file")?)?;
+    let ours_path = args.require(1, "our marked file")?;
+    let ours = SpriteData::load_marked(ours_path)?;
+    let theirs = SpriteData::load_marked(args.require(2, "their marked file")?)?;
+
+    let result = diff::merge(&base, &ours, &theirs);
+    for conflict in result.conflicts.iter() {
+        eprintln!("{}", conflict);
+    }
+
+    let output = args.option(&["-o", "--output"]).unwrap_or(ours_path);
+    let options = SaveOptions {
+        backups: 0,
+        ..Default::default()
+    };
+    marker::save_marked(output, &result.merged, &options)?;
+
+    Ok(if result.conflicts.is_empty() { 0 } else { 1 })
+}
@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs::File;
+use std::io::Write as IoWrite;
+use std::path::Path;
+
+use ggez::GameResult;
+
+use marker::*;
+use sprite::geom;
+
+/// Turns a sprite file name into an enum variant, `Bone (1).png` becomes `Bone1`.
+pub fn sanitize_name(name: &str) -> String {
+    let stem = match name.rfind('.') {
+        Some(ix) if ix > 0 => &name[..ix],
+        _ => name,
+    };
+
+    let mut ident = String::new();
+    for word in stem.split(|c: char| !c.is_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            ident.extend(first.to_uppercase());
+            ident.extend(chars);
+        }
+    }
+
+    // Words are capitalized, so `Self` is the only keyword that can come out.
+    match ident.chars().next() {
+        Some(c) if c.is_alphabetic() && ident == "Self" => ident + "_",
+        Some(c) if c.is_alphabetic() => ident,
+        _ => format!("S{}", ident),
+    }
+}
+
+fn variant_names(sprites: &[SpriteData]) -> Vec<String> {
+    let mut taken = HashSet::new();
+
+    sprites
+        .iter()
+        .map(|sd| {
+            let base = sanitize_name(&sd.name);
+            let mut name = base.clone();
+            let mut n = 2;
+            while !taken.insert(name.clone()) {
+                name = format!("{}_{}", base, n);
+                n += 1;
+            }
+            name
+        })
+        .collect()
+}
+
+fn rect_literal(r: &geom::Rect) -> String {
+    format!(
+        "Frame {{ x: {:?}, y: {:?}, w: {:?}, h: {:?} }}",
+        r.x, r.y, r.w, r.h
+    )
+}
+
+fn marker_literal(markers: &SpriteType) -> String {
+    match *markers {
+        SpriteType::Object => String::from("Marker::Object"),
+        SpriteType::Platform { ref horizontal } => {
+            let items: Vec<String> = horizontal
+                .iter()
+                .map(|h| format!("Horizontal::{:?}", h))
+                .collect();
+            format!("Marker::Platform(&[{}])", items.join(", "))
+        }
+        SpriteType::Ground { ref square } => {
+            let items: Vec<String> = square.iter().map(|s| format!("Square::{:?}", s)).collect();
+            format!("Marker::Ground(&[{}])", items.join(", "))
+        }
+        SpriteType::NineSlice { ref insets } => format!(
+            "Marker::NineSlice(Insets {{ left: {}, right: {}, top: {}, bottom: {} }})",
+            insets.left, insets.right, insets.top, insets.bottom
+        ),
+        SpriteType::GroundMask { ref mask } => format!("Marker::GroundMask({})", mask.blob()),
+    }
+}
+
+/// Squares of ground, masks the squares can't express have none.
+fn squares_literal(markers: &SpriteType) -> String {
+    let squares = match *markers {
+        SpriteType::Ground { ref square } => square.clone(),
+        SpriteType::GroundMask { ref mask } => mask.squares().unwrap_or_default(),
+        _ => vec![],
+    };
+    let items: Vec<String> = squares.iter().map(|s| format!("Square::{:?}", s)).collect();
+    items.join(", ")
+}
+
+const PRELUDE: &'static str = r#"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+/// Nine-slice borders in pixels from the frame edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Insets {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    Object,
+    Platform(&'static [Horizontal]),
+    Ground(&'static [Square]),
+    NineSlice(Insets),
+    /// Neighbours continuing the ground, N = 1 clockwise to NW = 128.
+    GroundMask(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteEntry {
+    pub id: SpriteId,
+    pub name: &'static str,
+    pub index: usize,
+    /// Atlas page of a multi-pack sheet.
+    pub page: usize,
+    pub frame: Frame,
+    pub uv: Frame,
+    pub marker: Marker,
+    /// Ground squares, those a ground mask converts to for masks.
+    pub squares: &'static [Square],
+    pub tags: &'static [&'static str],
+}
+"#;
+
+const HELPERS: &'static str = r#"
+impl SpriteId {
+    pub fn entry(self) -> &'static SpriteEntry {
+        &SPRITES[self as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        self.entry().name
+    }
+
+    pub fn frame(self) -> Frame {
+        self.entry().frame
+    }
+
+    pub fn uv(self) -> Frame {
+        self.entry().uv
+    }
+
+    pub fn marker(self) -> Marker {
+        self.entry().marker
+    }
+
+    pub fn tags(self) -> &'static [&'static str] {
+        self.entry().tags
+    }
+
+    pub fn from_name(name: &str) -> Option<SpriteId> {
+        SPRITES.iter().find(|e| e.name == name).map(|e| e.id)
+    }
+}
+
+pub fn objects() -> impl Iterator<Item = SpriteId> {
+    SPRITES
+        .iter()
+        .filter(|e| e.marker == Marker::Object)
+        .map(|e| e.id)
+}
+
+pub fn tagged<'a>(tag: &'a str) -> impl Iterator<Item = SpriteId> + 'a {
+    SPRITES
+        .iter()
+        .filter(move |e| e.tags.iter().any(|t| *t == tag))
+        .map(|e| e.id)
+}
+
+pub fn ground_with(square: Square) -> impl Iterator<Item = SpriteId> {
+    SPRITES
+        .iter()
+        .filter(move |e| e.squares.contains(&square))
+        .map(|e| e.id)
+}
+
+/// Sprites marked with exactly these neighbours as an 8-bit blob mask.
+pub fn ground_mask(neighbours: u8) -> impl Iterator<Item = SpriteId> {
+    SPRITES
+        .iter()
+        .filter(move |e| e.marker == Marker::GroundMask(neighbours))
+        .map(|e| e.id)
+}
+
+pub fn nine_slices() -> impl Iterator<Item = (SpriteId, Insets)> {
+    SPRITES.iter().filter_map(|e| match e.marker {
+        Marker::NineSlice(insets) => Some((e.id, insets)),
+        _ => None,
+    })
+}
+
+pub fn platform_with(horizontal: Horizontal) -> impl Iterator<Item = SpriteId> {
+    SPRITES
+        .iter()
+        .filter(move |e| match e.marker {
+            Marker::Platform(h) => h.contains(&horizontal),
+            _ => false,
+        })
+        .map(|e| e.id)
+}
+"#;
+
+/// Generates a self-contained Rust module describing the sheet: a `SpriteId`
+/// enum, a static `SPRITES` table with frames, markers and tags, and lookups
+/// such as `ground_with(Square::MT)` or `tagged("cave")`.
+pub fn generate(sprites: &[SpriteData]) -> String {
+    let names = variant_names(sprites);
+    let mut out = String::new();
+
+    writeln!(out, "// Generated by tilesheet-marker, do not edit.").unwrap();
+
+    writeln!(out, "\n#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]").unwrap();
+    writeln!(out, "pub enum Square {{").unwrap();
+    for si in square_icons.iter() {
+        writeln!(out, "    {:?},", si.s).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    writeln!(out, "\n#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]").unwrap();
+    writeln!(out, "pub enum Horizontal {{").unwrap();
+    for h in [Horizontal::Left, Horizontal::Right, Horizontal::Center].iter() {
+        writeln!(out, "    {:?},", h).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    writeln!(out, "\n#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]").unwrap();
+    writeln!(out, "pub enum SpriteId {{").unwrap();
+    for name in names.iter() {
+        writeln!(out, "    {},", name).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    out.push_str(PRELUDE);
+
+    writeln!(out, "\npub static SPRITES: [SpriteEntry; {}] = [", sprites.len()).unwrap();
+    for (sd, name) in sprites.iter().zip(names.iter()) {
+        writeln!(out, "    SpriteEntry {{").unwrap();
+        writeln!(out, "        id: SpriteId::{},", name).unwrap();
+        writeln!(out, "        name: {:?},", sd.name).unwrap();
+        writeln!(out, "        index: {},", sd.index).unwrap();
+        writeln!(out, "        page: {},", sd.page).unwrap();
+        writeln!(out, "        frame: {},", rect_literal(&sd.frame)).unwrap();
+        writeln!(out, "        uv: {},", rect_literal(&sd.on_screen_frame)).unwrap();
+        writeln!(out, "        marker: {},", marker_literal(&sd.markers)).unwrap();
+        writeln!(out, "        squares: &[{}],", squares_literal(&sd.markers)).unwrap();
+        writeln!(out, "        tags: &{:?},", sd.tags).unwrap();
+        writeln!(out, "    }},").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    out.push_str(HELPERS);
+    out
+}
+
+/// Writes the generated module to `path`, meant to be called from a `build.rs`
+/// and pulled in with `include!(concat!(env!("OUT_DIR"), "/sprites.rs"))`.
+pub fn write_source<P: AsRef<Path>>(path: P, sprites: &[SpriteData]) -> GameResult<()> {
+    let mut file = File::create(path)?;
+    file.write_all(generate(sprites).as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitized_names_are_identifiers() {
+        assert_eq!(sanitize_name("Bone (1).png"), "Bone1");
+        assert_eq!(sanitize_name("grass_top-left.png"), "GrassTopLeft");
+        assert_eq!(sanitize_name("self.png"), "Self_");
+        assert_eq!(sanitize_name("Self.png"), "Self_");
+        assert_eq!(sanitize_name("crate.png"), "Crate");
+        assert_eq!(sanitize_name("2x.png"), "S2x");
+        assert_eq!(sanitize_name("(1).png"), "S1");
+    }
+
+    #[test]
+    fn keyword_names_get_ids() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/level_ground-marked.json");
+        let first = SpriteData::load_marked(path).unwrap().remove(0);
+        let sprite = |name: &str| SpriteData {
+            name: String::from(name),
+            ..first.clone()
+        };
+        let sprites = vec![sprite("self.png"), sprite("Self.png"), sprite("fn.png")];
+        assert_eq!(variant_names(&sprites), vec!["Self_", "Self__2", "Fn"]);
+    }
+
+    #[test]
+    fn ground_masks_list_their_squares() {
+        let mask = GroundMask::new(MaskMode::Blob, mask::E | mask::S | mask::SE);
+        let markers = SpriteType::GroundMask { mask };
+        assert_eq!(squares_literal(&markers), "Square::LT");
+    }
+}
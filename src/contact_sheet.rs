@@ -0,0 +1,227 @@
+use image::{Rgba, RgbaImage};
+use rusttype::{point, Font, Scale};
+
+use marker::*;
+
+const FONT: &'static [u8] = include_bytes!("../resources/DejaVuSerif.ttf");
+
+const BACKGROUND: Rgba<u8> = Rgba([40, 40, 40, 255]);
+const GROUND: Rgba<u8> = Rgba([40, 200, 80, 255]);
+const INNER: Rgba<u8> = Rgba([240, 150, 30, 255]);
+const PLATFORM: Rgba<u8> = Rgba([50, 120, 240, 255]);
+const NINE_SLICE: Rgba<u8> = Rgba([230, 60, 200, 255]);
+const LABEL: Rgba<u8> = Rgba([230, 230, 230, 255]);
+
+#[derive(Debug, Clone)]
+pub struct ContactSheetOptions {
+    pub columns: usize,
+    /// Size of the square a sprite is scaled into, in pixels.
+    pub cell: u32,
+    pub padding: u32,
+    pub label_size: f32,
+    /// Atlas pixels per logical unit of the marked frames, 2 for an @2x atlas.
+    pub atlas_scale: f32,
+}
+
+impl Default for ContactSheetOptions {
+    fn default() -> ContactSheetOptions {
+        ContactSheetOptions {
+            columns: 6,
+            cell: 128,
+            padding: 12,
+            label_size: 14.0,
+            atlas_scale: 1.0,
+        }
+    }
+}
+
+fn fill(img: &mut RgbaImage, x: i64, y: i64, w: i64, h: i64, color: Rgba<u8>) {
+    let (iw, ih) = img.dimensions();
+    for py in y.max(0)..(y + h).min(ih as i64) {
+        for px in x.max(0)..(x + w).min(iw as i64) {
+            img.put_pixel(px as u32, py as u32, color);
+        }
+    }
+}
+
+fn blend(under: Rgba<u8>, over: Rgba<u8>, alpha: f32) -> Rgba<u8> {
+    let mut out = under;
+    for c in 0..3 {
+        let v = under.0[c] as f32 * (1.0 - alpha) + over.0[c] as f32 * alpha;
+        out.0[c] = v as u8;
+    }
+    out
+}
+
+fn draw_text(img: &mut RgbaImage, font: &Font, size: f32, x: i64, y: i64, text: &str) {
+    let scale = Scale::uniform(size);
+    let ascent = font.v_metrics(scale).ascent;
+    let (iw, ih) = img.dimensions();
+
+    for glyph in font.layout(text, scale, point(x as f32, y as f32 + ascent)) {
+        if let Some(bb) = glyph.pixel_bounding_box() {
+            glyph.draw(|gx, gy, v| {
+                let px = gx as i32 + bb.min.x;
+                let py = gy as i32 + bb.min.y;
+                if px >= 0 && py >= 0 && (px as u32) < iw && (py as u32) < ih {
+                    let under = *img.get_pixel(px as u32, py as u32);
+                    img.put_pixel(px as u32, py as u32, blend(under, LABEL, v));
+                }
+            });
+        }
+    }
+}
+
+/// Copies the sprite's frame from the atlas, scaled to fit the cell.
+fn blit_sprite(
+    img: &mut RgbaImage,
+    atlas: &RgbaImage,
+    atlas_scale: f32,
+    sd: &SpriteData,
+    x: u32,
+    y: u32,
+    cell: u32,
+) {
+    let f = sd.frame.scaled(atlas_scale);
+    let scale = cell as f32 / f.w.max(f.h);
+    let (w, h) = ((f.w * scale) as u32, (f.h * scale) as u32);
+    let (aw, ah) = atlas.dimensions();
+
+    for dy in 0..h {
+        for dx in 0..w {
+            let sx = (f.x + dx as f32 / scale) as u32;
+            let sy = (f.y + dy as f32 / scale) as u32;
+            if sx < aw && sy < ah {
+                let src = *atlas.get_pixel(sx, sy);
+                let under = *img.get_pixel(x + dx, y + dy);
+                let alpha = src.0[3] as f32 / 255.0;
+                img.put_pixel(x + dx, y + dy, blend(under, src, alpha));
+            }
+        }
+    }
+}
+
+/// Edge bars for ground squares and the open sides of ground masks, corner
+/// blocks for inner corners, caps for platform ends and guide lines for
+/// nine-slice borders, drawn over the cell.
+fn draw_markers(img: &mut RgbaImage, sd: &SpriteData, x: u32, y: u32, cell: u32) {
+    let (x, y, c) = (x as i64, y as i64, cell as i64);
+    let t = (c / 16).max(3);
+    let (top, left, bottom, right) = (
+        (x, y, c, t),
+        (x, y, t, c),
+        (x, y + c - t, c, t),
+        (x + c - t, y, t, c),
+    );
+
+    match sd.markers {
+        SpriteType::Object => (),
+        SpriteType::Ground { ref square } => {
+            for s in square.iter() {
+                let (bars, color) = match *s {
+                    Square::LT => (vec![top, left], GROUND),
+                    Square::MT => (vec![top], GROUND),
+                    Square::RT => (vec![top, right], GROUND),
+                    Square::LM => (vec![left], GROUND),
+                    Square::MM => (vec![(x + c / 2 - t, y + c / 2 - t, t * 2, t * 2)], GROUND),
+                    Square::RM => (vec![right], GROUND),
+                    Square::LB => (vec![left, bottom], GROUND),
+                    Square::MB => (vec![bottom], GROUND),
+                    Square::RB => (vec![right, bottom], GROUND),
+                    Square::ILT => (vec![(x, y, t * 3, t * 3)], INNER),
+                    Square::IRT => (vec![(x + c - t * 3, y, t * 3, t * 3)], INNER),
+                    Square::IBL => (vec![(x, y + c - t * 3, t * 3, t * 3)], INNER),
+                    Square::IBR => (vec![(x + c - t * 3, y + c - t * 3, t * 3, t * 3)], INNER),
+                };
+                for (bx, by, bw, bh) in bars {
+                    fill(img, bx, by, bw, bh, color);
+                }
+            }
+        }
+        SpriteType::GroundMask { mask: ref ground } => {
+            let blob = ground.blob();
+            let sides = [(mask::N, top), (mask::W, left), (mask::S, bottom), (mask::E, right)];
+            for &(side, (bx, by, bw, bh)) in sides.iter() {
+                if blob & side == 0 {
+                    fill(img, bx, by, bw, bh, GROUND);
+                }
+            }
+            // Corners that don't continue between two filled sides, like inner corners.
+            let k = t * 3;
+            for &(corner, a, b) in mask::CORNERS.iter() {
+                if blob & a != 0 && blob & b != 0 && blob & corner == 0 {
+                    let cx = if corner & (mask::NE | mask::SE) != 0 { x + c - k } else { x };
+                    let cy = if corner & (mask::SW | mask::SE) != 0 { y + c - k } else { y };
+                    fill(img, cx, cy, k, k, INNER);
+                }
+            }
+        }
+        SpriteType::Platform { ref horizontal } => {
+            for h in horizontal.iter() {
+                let cap = match *h {
+                    Horizontal::Left => (x, y, t, c / 3),
+                    Horizontal::Right => (x + c - t, y, t, c / 3),
+                    Horizontal::Center => (x + c / 3, y, c / 3, t),
+                };
+                fill(img, cap.0, cap.1, cap.2, cap.3, PLATFORM);
+            }
+        }
+        SpriteType::NineSlice { ref insets } => {
+            // Same scale `blit_sprite` uses, the sprite sits in the top left of the cell.
+            let scale = cell as f32 / sd.frame.w.max(sd.frame.h);
+            let (w, h) = ((sd.frame.w * scale) as i64, (sd.frame.h * scale) as i64);
+            let px = |v: u32| (v as f32 * scale) as i64;
+
+            fill(img, x + px(insets.left), y, 1, h, NINE_SLICE);
+            fill(img, x + w - px(insets.right) - 1, y, 1, h, NINE_SLICE);
+            fill(img, x, y + px(insets.top), w, 1, NINE_SLICE);
+            fill(img, x, y + h - px(insets.bottom) - 1, w, 1, NINE_SLICE);
+        }
+    }
+}
+
+/// Renders every sprite of the sheet into a grid with its markers and an
+/// `#index name [tags]` label, without opening a window. `atlases` holds the
+/// atlas of every page, sprites of a page without one are left blank.
+pub fn render(
+    marked: &[SpriteData],
+    atlases: &[RgbaImage],
+    opts: &ContactSheetOptions,
+) -> RgbaImage {
+    let font = Font::try_from_bytes(FONT).expect("Bundled font is broken");
+    let label_h = (opts.label_size * 1.4) as u32;
+    let cell_w = opts.cell + opts.padding;
+    let cell_h = opts.cell + label_h + opts.padding;
+
+    let columns = opts.columns.max(1);
+    let rows = (marked.len() + columns - 1) / columns;
+    let mut img = RgbaImage::from_pixel(
+        columns as u32 * cell_w + opts.padding,
+        rows.max(1) as u32 * cell_h + opts.padding,
+        BACKGROUND,
+    );
+
+    for (pos, sd) in marked.iter().enumerate() {
+        let x = (pos % columns) as u32 * cell_w + opts.padding;
+        let y = (pos / columns) as u32 * cell_h + opts.padding;
+
+        if let Some(atlas) = atlases.get(sd.page) {
+            blit_sprite(&mut img, atlas, opts.atlas_scale, sd, x, y, opts.cell);
+        }
+        draw_markers(&mut img, sd, x, y, opts.cell);
+
+        let mut label = format!("#{} {}", sd.index, sd.name);
+        if !sd.tags.is_empty() {
+            label.push_str(&format!(" [{}]", sd.tags.join(", ")));
+        }
+        draw_text(
+            &mut img,
+            &font,
+            opts.label_size,
+            x as i64,
+            (y + opts.cell) as i64 + 2,
+            &label,
+        );
+    }
+    img
+}
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use image::{Rgba, RgbaImage};
+
+use marker::*;
+
+pub use marker::mask::{canonical, mask_name, required_squares};
+pub use marker::mask::{E, N, NE, NW, S, SE, SW, W};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobMode {
+    /// Corners only count when both adjacent sides are filled.
+    Blob47,
+    /// Every neighbour configuration, reduced to its blob tile.
+    Full256,
+}
+
+/// The 47 distinct blob tiles.
+pub fn blob_configurations() -> Vec<u8> {
+    let mut all: Vec<u8> = (0..256u16)
+        .map(|m| canonical(m as u8))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    all.sort();
+    all
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Status {
+    /// Names of the sprites marked with exactly the required squares, or
+    /// with a mask of the configuration.
+    Covered(Vec<String>),
+    Missing,
+    Unrepresentable,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Configuration {
+    pub mask: u8,
+    pub neighbours: String,
+    pub required: Option<Vec<Square>>,
+    pub status: Status,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CoverageReport {
+    pub mode: BlobMode,
+    pub configurations: Vec<Configuration>,
+}
+
+/// Checks which neighbour configurations the sheet's ground tiles can fill.
+/// Squares can't express every configuration, ground masks can.
+pub fn analyse(marked: &[SpriteData], mode: BlobMode) -> CoverageReport {
+    let grounds: Vec<(&str, HashSet<&Square>)> = marked
+        .iter()
+        .filter_map(|sd| match sd.markers {
+            SpriteType::Ground { ref square } => Some((sd.name.as_str(), square.iter().collect())),
+            _ => None,
+        })
+        .collect();
+    let masked: Vec<(&str, u8)> = marked
+        .iter()
+        .filter_map(|sd| match sd.markers {
+            SpriteType::GroundMask { ref mask } => Some((sd.name.as_str(), mask.blob())),
+            _ => None,
+        })
+        .collect();
+
+    let masks: Vec<u8> = match mode {
+        BlobMode::Blob47 => blob_configurations(),
+        BlobMode::Full256 => (0..256u16).map(|m| m as u8).collect(),
+    };
+
+    let configurations = masks
+        .into_iter()
+        .map(|mask| {
+            let required = required_squares(mask);
+            let mut names: Vec<String> = match required {
+                Some(ref req) => {
+                    let wanted: HashSet<&Square> = req.iter().collect();
+                    grounds
+                        .iter()
+                        .filter(|&&(_, ref have)| *have == wanted)
+                        .map(|&(name, _)| String::from(name))
+                        .collect()
+                }
+                None => vec![],
+            };
+            names.extend(
+                masked
+                    .iter()
+                    .filter(|&&(_, blob)| blob == canonical(mask))
+                    .map(|&(name, _)| String::from(name)),
+            );
+
+            let status = if !names.is_empty() {
+                Status::Covered(names)
+            } else if required.is_none() {
+                Status::Unrepresentable
+            } else {
+                Status::Missing
+            };
+            Configuration {
+                mask,
+                neighbours: mask_name(mask),
+                required,
+                status,
+            }
+        })
+        .collect();
+
+    CoverageReport {
+        mode,
+        configurations,
+    }
+}
+
+impl CoverageReport {
+    pub fn missing(&self) -> Vec<&Configuration> {
+        self.configurations
+            .iter()
+            .filter(|c| c.status == Status::Missing)
+            .collect()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let mut covered = 0;
+        let mut unrepresentable = 0;
+
+        for c in self.configurations.iter() {
+            let required = match c.required {
+                Some(ref r) => format!("{:?}", r),
+                None => String::from("-"),
+            };
+            let status = match c.status {
+                Status::Covered(ref names) => {
+                    covered += 1;
+                    format!("covered by {}", names.join(", "))
+                }
+                Status::Missing => String::from("MISSING"),
+                Status::Unrepresentable => {
+                    unrepresentable += 1;
+                    String::from("not expressible with squares")
+                }
+            };
+            writeln!(
+                out,
+                "{:08b} [{}] {}: {}",
+                c.mask, c.neighbours, required, status
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            out,
+            "\n{} configurations: {} covered, {} missing, {} not expressible",
+            self.configurations.len(),
+            covered,
+            self.missing().len(),
+            unrepresentable
+        )
+        .unwrap();
+        out
+    }
+
+    /// Chart of the missing configurations, one 3x3 neighbourhood per cell with
+    /// the tile to draw in the middle.
+    pub fn render_missing(&self) -> RgbaImage {
+        let block = 12;
+        let cell = block * 3 + 8;
+        let columns = 8;
+
+        let missing = self.missing();
+        let rows = (missing.len() + columns - 1) / columns;
+        let mut img = RgbaImage::from_pixel(
+            (columns * cell) as u32,
+            (rows.max(1) * cell) as u32,
+            Rgba([32, 32, 32, 255]),
+        );
+
+        let grid = [[NW, N, NE], [W, 0, E], [SW, S, SE]];
+        for (ix, c) in missing.iter().enumerate() {
+            let cx = (ix % columns) * cell + 4;
+            let cy = (ix / columns) * cell + 4;
+
+            for (gy, row) in grid.iter().enumerate() {
+                for (gx, &bit) in row.iter().enumerate() {
+                    let color = if bit == 0 {
+                        Rgba([220, 40, 40, 255])
+                    } else if c.mask & bit != 0 {
+                        Rgba([60, 180, 80, 255])
+                    } else {
+                        Rgba([70, 70, 70, 255])
+                    };
+                    for y in 1..block {
+                        for x in 1..block {
+                            let px = (cx + gx * block + x) as u32;
+                            let py = (cy + gy * block + y) as u32;
+                            img.put_pixel(px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+        img
+    }
+}
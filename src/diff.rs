@@ -0,0 +1,418 @@
+//! Semantic diff and three-way merge of marked data, matching sprites by name.
+//!
+//! `marker merge` can be used as a git merge driver:
+//!
+//! ```text
+//! # .gitattributes
+//! *-marked.json merge=marker
+//!
+//! # .git/config
+//! [merge "marker"]
+//!     name = marked sprite sheet merge
+//!     driver = marker merge %O %A %B
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+
+use marker::*;
+use sprite::geom;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(SpriteData),
+    Removed(SpriteData),
+    Markers {
+        name: String,
+        from: SpriteType,
+        to: SpriteType,
+    },
+    Frame {
+        name: String,
+        from: geom::Rect,
+        to: geom::Rect,
+    },
+    Index {
+        name: String,
+        from: usize,
+        to: usize,
+    },
+    Pivot {
+        name: String,
+        from: geom::Point,
+        to: geom::Point,
+    },
+    Tags {
+        name: String,
+        from: Vec<String>,
+        to: Vec<String>,
+    },
+}
+
+fn describe(markers: &SpriteType) -> String {
+    match *markers {
+        SpriteType::Object => String::from("Object"),
+        SpriteType::Platform { ref horizontal } => format!("Platform {:?}", horizontal),
+        SpriteType::Ground { ref square } => format!("Ground {:?}", square),
+        SpriteType::NineSlice { ref insets } => format!(
+            "NineSlice l{} r{} t{} b{}",
+            insets.left, insets.right, insets.top, insets.bottom
+        ),
+        SpriteType::GroundMask { mask: ref ground } => format!(
+            "GroundMask {:?} [{}]",
+            ground.mode,
+            mask::mask_name(ground.bits)
+        ),
+    }
+}
+
+fn set_delta<T: fmt::Debug + Eq + Hash>(from: &[T], to: &[T]) -> String {
+    let from_set: HashSet<&T> = from.iter().collect();
+    let to_set: HashSet<&T> = to.iter().collect();
+    let mut parts: Vec<String> = to
+        .iter()
+        .filter(|x| !from_set.contains(x))
+        .map(|x| format!("+{:?}", x))
+        .collect();
+    parts.extend(
+        from.iter()
+            .filter(|x| !to_set.contains(x))
+            .map(|x| format!("-{:?}", x)),
+    );
+    parts.join(" ")
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::Added(ref sd) => write!(f, "+ {}: {}", sd.name, describe(&sd.markers)),
+            Change::Removed(ref sd) => write!(f, "- {}: {}", sd.name, describe(&sd.markers)),
+            Change::Markers {
+                ref name,
+                ref from,
+                ref to,
+            } => match (from, to) {
+                (&SpriteType::Ground { square: ref a }, &SpriteType::Ground { square: ref b }) => {
+                    write!(f, "~ {}: Ground {}", name, set_delta(a, b))
+                }
+                (
+                    &SpriteType::Platform { horizontal: ref a },
+                    &SpriteType::Platform { horizontal: ref b },
+                ) => write!(f, "~ {}: Platform {}", name, set_delta(a, b)),
+                _ => write!(f, "~ {}: {} -> {}", name, describe(from), describe(to)),
+            },
+            Change::Frame {
+                ref name,
+                ref from,
+                ref to,
+            } => write!(f, "~ {}: frame {:?} -> {:?}", name, from, to),
+            Change::Index { ref name, from, to } => {
+                write!(f, "~ {}: index {} -> {}", name, from, to)
+            }
+            Change::Pivot {
+                ref name,
+                ref from,
+                ref to,
+            } => write!(
+                f,
+                "~ {}: pivot ({}, {}) -> ({}, {})",
+                name, from.x, from.y, to.x, to.y
+            ),
+            Change::Tags {
+                ref name,
+                ref from,
+                ref to,
+            } => write!(f, "~ {}: tags {}", name, set_delta(from, to)),
+        }
+    }
+}
+
+fn by_name(data: &[SpriteData]) -> HashMap<&str, &SpriteData> {
+    data.iter().map(|sd| (sd.name.as_str(), sd)).collect()
+}
+
+/// Changes turning `a` into `b`.
+pub fn diff(a: &[SpriteData], b: &[SpriteData]) -> Vec<Change> {
+    let a_names = by_name(a);
+    let b_names = by_name(b);
+    let mut changes = vec![];
+
+    for old in a.iter() {
+        match b_names.get(old.name.as_str()) {
+            None => changes.push(Change::Removed(old.clone())),
+            Some(new) => {
+                if old.markers != new.markers {
+                    changes.push(Change::Markers {
+                        name: old.name.clone(),
+                        from: old.markers.clone(),
+                        to: new.markers.clone(),
+                    });
+                }
+                if old.frame != new.frame {
+                    changes.push(Change::Frame {
+                        name: old.name.clone(),
+                        from: old.frame.clone(),
+                        to: new.frame.clone(),
+                    });
+                }
+                if old.index != new.index {
+                    changes.push(Change::Index {
+                        name: old.name.clone(),
+                        from: old.index,
+                        to: new.index,
+                    });
+                }
+                if old.pivot != new.pivot {
+                    changes.push(Change::Pivot {
+                        name: old.name.clone(),
+                        from: old.pivot.clone(),
+                        to: new.pivot.clone(),
+                    });
+                }
+                if old.tags != new.tags {
+                    changes.push(Change::Tags {
+                        name: old.name.clone(),
+                        from: old.tags.clone(),
+                        to: new.tags.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    for new in b.iter() {
+        if !a_names.contains_key(new.name.as_str()) {
+            changes.push(Change::Added(new.clone()));
+        }
+    }
+    changes
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub name: String,
+    pub base: Option<SpriteData>,
+    pub ours: Option<SpriteData>,
+    pub theirs: Option<SpriteData>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = |sd: &Option<SpriteData>| match *sd {
+            Some(ref sd) => describe(&sd.markers),
+            None => String::from("removed"),
+        };
+        write!(
+            f,
+            "! {}: base {}, ours {}, theirs {}",
+            self.name,
+            side(&self.base),
+            side(&self.ours),
+            side(&self.theirs)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Merge {
+    /// Merged data, conflicting sprites keep our side.
+    pub merged: Vec<SpriteData>,
+    pub conflicts: Vec<Conflict>,
+}
+
+fn merge_value<T: PartialEq + Clone>(base: &T, ours: &T, theirs: &T) -> Option<T> {
+    if ours == theirs || base == theirs {
+        Some(ours.clone())
+    } else if base == ours {
+        Some(theirs.clone())
+    } else {
+        None
+    }
+}
+
+/// Applies both sides' additions and removals to the base set, in our order
+/// with their additions at the end.
+fn merge_set<T: PartialEq + Clone>(base: &[T], ours: &[T], theirs: &[T]) -> Vec<T> {
+    let mut result: Vec<T> = ours
+        .iter()
+        .filter(|x| !base.contains(x) || theirs.contains(x))
+        .cloned()
+        .collect();
+    for x in theirs.iter() {
+        if !base.contains(x) && !result.contains(x) {
+            result.push(x.clone());
+        }
+    }
+    result
+}
+
+/// Neighbours both sides kept plus those either side added, like `merge_set`.
+fn merge_bits(base: u8, ours: u8, theirs: u8) -> u8 {
+    (ours & theirs) | ((ours | theirs) & !base)
+}
+
+fn merge_markers(base: &SpriteType, ours: &SpriteType, theirs: &SpriteType) -> Option<SpriteType> {
+    if let Some(m) = merge_value(base, ours, theirs) {
+        return Some(m);
+    }
+    match (base, ours, theirs) {
+        (
+            &SpriteType::Ground { square: ref b },
+            &SpriteType::Ground { square: ref o },
+            &SpriteType::Ground { square: ref t },
+        ) => Some(SpriteType::Ground {
+            square: merge_set(b, o, t),
+        }),
+        (
+            &SpriteType::Platform { horizontal: ref b },
+            &SpriteType::Platform { horizontal: ref o },
+            &SpriteType::Platform { horizontal: ref t },
+        ) => Some(SpriteType::Platform {
+            horizontal: merge_set(b, o, t),
+        }),
+        (
+            &SpriteType::GroundMask { mask: b },
+            &SpriteType::GroundMask { mask: o },
+            &SpriteType::GroundMask { mask: t },
+        ) if b.mode == o.mode && o.mode == t.mode => Some(SpriteType::GroundMask {
+            mask: GroundMask::new(o.mode, merge_bits(b.bits, o.bits, t.bits)),
+        }),
+        _ => None,
+    }
+}
+
+fn merge_sprite(base: &SpriteData, ours: &SpriteData, theirs: &SpriteData) -> Option<SpriteData> {
+    let markers = merge_markers(&base.markers, &ours.markers, &theirs.markers)?;
+    let frame = merge_value(&base.frame, &ours.frame, &theirs.frame)?;
+    let on_screen_frame = merge_value(
+        &base.on_screen_frame,
+        &ours.on_screen_frame,
+        &theirs.on_screen_frame,
+    )?;
+    let index = merge_value(&base.index, &ours.index, &theirs.index)?;
+    let page = merge_value(&base.page, &ours.page, &theirs.page)?;
+    let pivot = merge_value(&base.pivot, &ours.pivot, &theirs.pivot)?;
+    let tags = merge_set(&base.tags, &ours.tags, &theirs.tags);
+
+    Some(SpriteData {
+        on_screen_frame,
+        frame,
+        markers,
+        name: ours.name.clone(),
+        index,
+        page,
+        pivot,
+        tags,
+    })
+}
+
+/// Three-way merge of marked data. Edits to different sprites, or to
+/// different squares/caps/neighbours/tags of the same sprite, are combined;
+/// anything else touched on both sides is a conflict.
+pub fn merge(base: &[SpriteData], ours: &[SpriteData], theirs: &[SpriteData]) -> Merge {
+    let base_names = by_name(base);
+    let our_names = by_name(ours);
+    let their_names = by_name(theirs);
+
+    let mut names: Vec<&str> = ours.iter().map(|sd| sd.name.as_str()).collect();
+    for sd in theirs.iter().chain(base.iter()) {
+        if !names.contains(&sd.name.as_str()) {
+            names.push(sd.name.as_str());
+        }
+    }
+
+    let mut merged = vec![];
+    let mut conflicts = vec![];
+
+    for name in names {
+        let b = base_names.get(name).cloned();
+        let o = our_names.get(name).cloned();
+        let t = their_names.get(name).cloned();
+
+        let result = match (b, o, t) {
+            (Some(b), Some(o), Some(t)) => merge_sprite(b, o, t).map(Some),
+            _ => merge_value(&b, &o, &t).map(|sd| sd.cloned()),
+        };
+
+        match result {
+            Some(Some(sd)) => merged.push(sd),
+            Some(None) => (),
+            None => {
+                conflicts.push(Conflict {
+                    name: String::from(name),
+                    base: b.cloned(),
+                    ours: o.cloned(),
+                    theirs: t.cloned(),
+                });
+                if let Some(o) = o {
+                    merged.push(o.clone());
+                }
+            }
+        }
+    }
+
+    Merge { merged, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level_ground() -> Vec<SpriteData> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/level_ground-marked.json");
+        SpriteData::load_marked(path).unwrap()
+    }
+
+    fn with_mask(marked: &[SpriteData], bits: u8) -> Vec<SpriteData> {
+        let mut marked = marked.to_vec();
+        marked[4].markers = SpriteType::GroundMask {
+            mask: GroundMask::new(MaskMode::Sides, bits),
+        };
+        marked
+    }
+
+    fn with_tags(marked: &[SpriteData], tags: &[&str]) -> Vec<SpriteData> {
+        let mut marked = marked.to_vec();
+        marked[4].tags = tags.iter().map(|t| String::from(*t)).collect();
+        marked
+    }
+
+    #[test]
+    fn mask_edits_to_different_neighbours_merge() {
+        let marked = level_ground();
+        let base = with_mask(&marked, mask::N | mask::E);
+        let ours = with_mask(&marked, mask::N | mask::E | mask::S);
+        let theirs = with_mask(&marked, mask::N);
+
+        let result = merge(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        let merged = result.merged.iter().find(|sd| sd.name == marked[4].name).unwrap();
+        let bits = merged.markers.ground_mask().unwrap().bits;
+        assert_eq!(bits, mask::N | mask::S);
+    }
+
+    #[test]
+    fn mask_mode_changes_conflict() {
+        let marked = level_ground();
+        let base = with_mask(&marked, mask::N);
+        let ours = with_mask(&marked, mask::N | mask::S);
+        let mut theirs = base.clone();
+        theirs[4].markers = SpriteType::GroundMask {
+            mask: GroundMask::new(MaskMode::Blob, mask::N),
+        };
+        assert_eq!(merge(&base, &ours, &theirs).conflicts.len(), 1);
+    }
+
+    #[test]
+    fn merged_tags_keep_our_order() {
+        let marked = level_ground();
+        let base = with_tags(&marked, &["zone", "cave"]);
+        let ours = with_tags(&marked, &["zone", "cave", "dark"]);
+        let theirs = with_tags(&marked, &["zone", "ice"]);
+
+        let result = merge(&base, &ours, &theirs);
+        let merged = result.merged.iter().find(|sd| sd.name == marked[4].name).unwrap();
+        assert_eq!(merged.tags, vec!["zone", "dark", "ice"]);
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt::Write;
+
+use super::{atlas_frame, grid_size};
+use marker::*;
+use sprite::texture_packer::SpriteSheetMeta;
+
+const TYPE_INT: usize = 2;
+const TYPE_STRING: usize = 4;
+const TYPE_VECTOR4I: usize = 13;
+const TYPE_PACKED_STRING_ARRAY: usize = 34;
+
+/// Terrain peering bits of a tile, i.e. neighbours that continue the ground.
+fn peering_bits(neighbours: u8) -> Vec<&'static str> {
+    let all = [
+        (mask::E, "right_side"),
+        (mask::SE, "bottom_right_corner"),
+        (mask::S, "bottom_side"),
+        (mask::SW, "bottom_left_corner"),
+        (mask::W, "left_side"),
+        (mask::NW, "top_left_corner"),
+        (mask::N, "top_side"),
+        (mask::NE, "top_right_corner"),
+    ];
+    all.iter()
+        .filter(|&&(bit, _)| neighbours & bit != 0)
+        .map(|&(_, name)| name)
+        .collect()
+}
+
+fn type_name(markers: &SpriteType) -> &'static str {
+    match *markers {
+        SpriteType::Object => "Object",
+        SpriteType::Platform { .. } => "Platform",
+        SpriteType::Ground { .. } | SpriteType::GroundMask { .. } => "Ground",
+        SpriteType::NineSlice { .. } => "NineSlice",
+    }
+}
+
+fn string_array(values: &[String]) -> String {
+    let quoted: Vec<String> = values.iter().map(|v| format!("{:?}", v)).collect();
+    format!("PackedStringArray({})", quoted.join(", "))
+}
+
+/// Tiles of one page's atlas source, `cell` is the tile size in atlas pixels.
+fn write_tiles<'a, I>(out: &mut String, sprites: I, meta: &SpriteSheetMeta, cell: u32)
+where
+    I: Iterator<Item = &'a SpriteData>,
+{
+    let scale = meta.scale_factor();
+    for sd in sprites {
+        let f = atlas_frame(sd, meta);
+        let coords = format!("{}:{}", f.x as u32 / cell, f.y as u32 / cell);
+        let (w, h) = (f.w as u32 / cell, f.h as u32 / cell);
+
+        writeln!(out, "{}/0 = 0", coords).unwrap();
+        if w != 1 || h != 1 {
+            writeln!(out, "{}/size_in_atlas = Vector2i({}, {})", coords, w, h).unwrap();
+        }
+
+        let squares = match sd.markers {
+            SpriteType::Ground { ref square } => square.clone(),
+            SpriteType::GroundMask { ref mask } => mask.squares().unwrap_or_default(),
+            _ => vec![],
+        };
+        let squares: Vec<String> = squares.iter().map(|s| format!("{:?}", s)).collect();
+        // Neighbours each alternative connects to.
+        let terrains: Vec<u8> = match sd.markers {
+            SpriteType::Ground { ref square } => square.iter().map(|s| !s.open_bits()).collect(),
+            SpriteType::GroundMask { ref mask } => vec![mask.blob()],
+            _ => vec![],
+        };
+        let horizontal: Vec<String> = match sd.markers {
+            SpriteType::Platform { ref horizontal } => {
+                horizontal.iter().map(|h| format!("{:?}", h)).collect()
+            }
+            _ => vec![],
+        };
+        let alternatives = terrains.len().max(1);
+
+        for alt in 0..alternatives {
+            let tile = format!("{}/{}", coords, alt);
+            if alt > 0 {
+                writeln!(out, "{} = {}", tile, alt).unwrap();
+            }
+            writeln!(
+                out,
+                "{}/custom_data_0 = \"{}\"",
+                tile,
+                type_name(&sd.markers)
+            )
+            .unwrap();
+            writeln!(out, "{}/custom_data_1 = {}", tile, string_array(&squares)).unwrap();
+            writeln!(
+                out,
+                "{}/custom_data_2 = {}",
+                tile,
+                string_array(&horizontal)
+            )
+            .unwrap();
+            if let Some(i) = sd.markers.insets() {
+                writeln!(
+                    out,
+                    "{}/custom_data_3 = Vector4i({}, {}, {}, {})",
+                    tile,
+                    (i.left as f32 * scale) as u32,
+                    (i.top as f32 * scale) as u32,
+                    (i.right as f32 * scale) as u32,
+                    (i.bottom as f32 * scale) as u32
+                )
+                .unwrap();
+            }
+            if !sd.tags.is_empty() {
+                writeln!(out, "{}/custom_data_4 = {}", tile, string_array(&sd.tags)).unwrap();
+            }
+            if let Some(mask) = sd.markers.ground_mask() {
+                writeln!(out, "{}/custom_data_5 = {}", tile, mask.blob()).unwrap();
+            }
+
+            if let Some(&mask) = terrains.get(alt) {
+                writeln!(out, "{}/terrain_set = 0", tile).unwrap();
+                writeln!(out, "{}/terrain = 0", tile).unwrap();
+                for bit in peering_bits(mask) {
+                    writeln!(out, "{}/terrains_peering_bit/{} = 0", tile, bit).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Renders a Godot 4 `TileSet` resource for the marked sheet, with an atlas
+/// source for every page of a multi-pack sheet.
+///
+/// `texture_paths` are the resource paths of the page atlases as seen from
+/// the Godot project, e.g. `res://tiles/level_ground.png`. Every ground
+/// square becomes its own alternative tile, so a tile marked with several
+/// squares can still take part in terrain matching for each of them. A
+/// ground mask is a single tile peering with exactly its neighbours.
+pub fn tileset(
+    marked: &[SpriteData],
+    pages: &[SpriteSheetMeta],
+    texture_paths: &[String],
+) -> String {
+    // Pages of a multi-pack share the scale, so they share the grid too.
+    let cell = grid_size(marked, &pages[0]);
+    let mut out = String::new();
+
+    // Godot counts every ext and sub resource plus the tile set itself.
+    let (ext_resources, sub_resources) = (texture_paths.len(), pages.len());
+    writeln!(
+        out,
+        "[gd_resource type=\"TileSet\" load_steps={} format=3]\n",
+        ext_resources + sub_resources + 1
+    )
+    .unwrap();
+
+    for (page, path) in texture_paths.iter().enumerate() {
+        writeln!(
+            out,
+            "[ext_resource type=\"Texture2D\" path=\"{}\" id=\"{}_atlas\"]",
+            path,
+            page + 1
+        )
+        .unwrap();
+    }
+
+    for (page, meta) in pages.iter().enumerate() {
+        writeln!(
+            out,
+            "\n[sub_resource type=\"TileSetAtlasSource\" id=\"TileSetAtlasSource_{}\"]",
+            page + 1
+        )
+        .unwrap();
+        writeln!(out, "resource_name = \"{}\"", meta.image).unwrap();
+        writeln!(out, "texture = ExtResource(\"{}_atlas\")", page + 1).unwrap();
+        writeln!(out, "texture_region_size = Vector2i({}, {})", cell, cell).unwrap();
+        let sprites = marked.iter().filter(|sd| sd.page == page);
+        write_tiles(&mut out, sprites, meta, cell);
+    }
+
+    writeln!(out, "\n[resource]").unwrap();
+    writeln!(out, "tile_size = Vector2i({}, {})", cell, cell).unwrap();
+    writeln!(out, "terrain_set_0/mode = 0").unwrap();
+    writeln!(out, "terrain_set_0/terrain_0/name = \"Ground\"").unwrap();
+    writeln!(out, "terrain_set_0/terrain_0/color = Color(0, 0.8, 0.2, 1)").unwrap();
+    writeln!(out, "custom_data_layer_0/name = \"marker_type\"").unwrap();
+    writeln!(out, "custom_data_layer_0/type = {}", TYPE_STRING).unwrap();
+    writeln!(out, "custom_data_layer_1/name = \"squares\"").unwrap();
+    writeln!(
+        out,
+        "custom_data_layer_1/type = {}",
+        TYPE_PACKED_STRING_ARRAY
+    )
+    .unwrap();
+    writeln!(out, "custom_data_layer_2/name = \"horizontal\"").unwrap();
+    writeln!(
+        out,
+        "custom_data_layer_2/type = {}",
+        TYPE_PACKED_STRING_ARRAY
+    )
+    .unwrap();
+    // Left, top, right, bottom like the patch margins of `NinePatchRect`.
+    writeln!(out, "custom_data_layer_3/name = \"nine_slice\"").unwrap();
+    writeln!(out, "custom_data_layer_3/type = {}", TYPE_VECTOR4I).unwrap();
+    writeln!(out, "custom_data_layer_4/name = \"tags\"").unwrap();
+    writeln!(
+        out,
+        "custom_data_layer_4/type = {}",
+        TYPE_PACKED_STRING_ARRAY
+    )
+    .unwrap();
+    // 8-bit blob mask of the neighbours, N = 1 clockwise to NW = 128.
+    writeln!(out, "custom_data_layer_5/name = \"neighbours\"").unwrap();
+    writeln!(out, "custom_data_layer_5/type = {}", TYPE_INT).unwrap();
+    for page in 0..pages.len() {
+        writeln!(
+            out,
+            "sources/{} = SubResource(\"TileSetAtlasSource_{}\")",
+            page,
+            page + 1
+        )
+        .unwrap();
+    }
+
+    out
+}
@@ -0,0 +1,290 @@
+use std::collections::{HashMap, HashSet};
+
+use marker::*;
+use sprite::texture_packer::SpriteSheetInfo;
+use super::{atlas_frame, grid_size};
+
+const ENUM_UID: i64 = 1;
+/// Uid of the first page's tileset, the next pages count up from it.
+const TILESET_UID: i64 = 2;
+
+/// Enum and tileset definitions, shaped like the `defs` section of an LDtk project.
+#[derive(Serialize, Debug, Clone)]
+pub struct LdtkDefs {
+    pub enums: Vec<EnumDef>,
+    pub tilesets: Vec<TilesetDef>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[allow(non_snake_case)]
+pub struct EnumDef {
+    pub identifier: String,
+    pub uid: i64,
+    pub values: Vec<EnumValue>,
+    pub iconTilesetUid: Option<i64>,
+    pub externalRelPath: Option<String>,
+    pub externalFileChecksum: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[allow(non_snake_case)]
+pub struct EnumValue {
+    pub id: String,
+    pub tileRect: Option<TileRect>,
+    pub color: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[allow(non_snake_case)]
+pub struct TileRect {
+    pub tilesetUid: i64,
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[allow(non_snake_case)]
+pub struct TilesetDef {
+    pub __cWid: u32,
+    pub __cHei: u32,
+    pub identifier: String,
+    pub uid: i64,
+    pub relPath: String,
+    pub embedAtlas: Option<String>,
+    pub pxWid: u32,
+    pub pxHei: u32,
+    pub tileGridSize: u32,
+    pub spacing: u32,
+    pub padding: u32,
+    pub tags: Vec<String>,
+    pub tagsSourceEnumUid: Option<i64>,
+    pub enumTags: Vec<EnumTag>,
+    pub customData: Vec<TileCustomData>,
+    pub savedSelections: Vec<String>,
+    pub cachedPixelData: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[allow(non_snake_case)]
+pub struct EnumTag {
+    pub enumValueId: String,
+    pub tileIds: Vec<u32>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[allow(non_snake_case)]
+pub struct TileCustomData {
+    pub tileId: u32,
+    pub data: String,
+}
+
+/// Every enum value id a sprite can be tagged with, in declaration order.
+pub fn enum_value_ids() -> Vec<String> {
+    let mut ids = vec![String::from("Object"), String::from("Platform")];
+    for h in [Horizontal::Left, Horizontal::Center, Horizontal::Right].iter() {
+        ids.push(format!("Platform_{:?}", h));
+    }
+    ids.push(String::from("Ground"));
+    for si in square_icons.iter() {
+        ids.push(format!("Ground_{:?}", si.s));
+    }
+    ids.push(String::from("NineSlice"));
+    ids
+}
+
+/// Enum value ids of a single sprite, e.g. `Ground` and `Ground_MT`.
+pub fn marker_tags(markers: &SpriteType) -> Vec<String> {
+    match *markers {
+        SpriteType::Object => vec![String::from("Object")],
+        SpriteType::Platform { ref horizontal } => {
+            let mut tags = vec![String::from("Platform")];
+            tags.extend(horizontal.iter().map(|h| format!("Platform_{:?}", h)));
+            tags
+        }
+        SpriteType::Ground { ref square } => {
+            let mut tags = vec![String::from("Ground")];
+            tags.extend(square.iter().map(|s| format!("Ground_{:?}", s)));
+            tags
+        }
+        SpriteType::NineSlice { .. } => vec![String::from("NineSlice")],
+        // Squares only when they can express the mask, the tile is ground anyway.
+        SpriteType::GroundMask { ref mask } => {
+            let mut tags = vec![String::from("Ground")];
+            let squares = mask.squares().unwrap_or_default();
+            tags.extend(squares.iter().map(|s| format!("Ground_{:?}", s)));
+            tags
+        }
+    }
+}
+
+/// Enum value id of a sprite tag, `ice-cave` becomes `Tag_ice_cave`.
+pub fn tag_value_id(tag: &str) -> String {
+    let ident: String = tag.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("Tag_{}", ident)
+}
+
+/// Enum value ids of every tag. Tags normalizing to the same id, like
+/// `foo-bar` and `foo_bar`, are numbered since LDtk rejects repeated values.
+pub fn tag_value_ids(tags: &[String]) -> Vec<(String, String)> {
+    let mut taken = HashSet::new();
+    tags.iter()
+        .map(|tag| {
+            let base = tag_value_id(tag);
+            let mut id = base.clone();
+            let mut n = 2;
+            while !taken.insert(id.clone()) {
+                id = format!("{}_{}", base, n);
+                n += 1;
+            }
+            (tag.clone(), id)
+        })
+        .collect()
+}
+
+fn color_of(id: &str) -> u32 {
+    if id.starts_with("Tag_") {
+        0xaa_55_cc
+    } else if id.starts_with("Ground") {
+        0x33_cc_55
+    } else if id.starts_with("Platform") {
+        0x33_77_ee
+    } else {
+        0xcc_aa_33
+    }
+}
+
+/// Tileset of one page, tagged with the `Markers` enum values in `ids`.
+fn tileset_def(
+    info: &SpriteSheetInfo,
+    sprites: &[&SpriteData],
+    ids: &[String],
+    tag_ids: &HashMap<String, String>,
+    grid: u32,
+    identifier: String,
+    uid: i64,
+) -> TilesetDef {
+    let px_w = info.meta.size.w as u32;
+    let px_h = info.meta.size.h as u32;
+    let c_wid = px_w / grid;
+
+    let mut enum_tags: Vec<EnumTag> = ids.iter()
+        .map(|id| EnumTag {
+            enumValueId: id.clone(),
+            tileIds: vec![],
+        })
+        .collect();
+    let mut custom_data = vec![];
+
+    for sd in sprites.iter() {
+        let f = atlas_frame(sd, &info.meta);
+        let tile_id = (f.y as u32 / grid) * c_wid + f.x as u32 / grid;
+
+        let tags = sd.tags.iter().filter_map(|tag| tag_ids.get(tag).cloned());
+        for tag in marker_tags(&sd.markers).into_iter().chain(tags) {
+            if let Some(et) = enum_tags.iter_mut().find(|et| et.enumValueId == tag) {
+                et.tileIds.push(tile_id);
+            }
+        }
+        custom_data.push(TileCustomData {
+            tileId: tile_id,
+            data: sd.name.clone(),
+        });
+    }
+
+    TilesetDef {
+        __cWid: c_wid,
+        __cHei: px_h / grid,
+        identifier,
+        uid,
+        relPath: info.meta.image.clone(),
+        embedAtlas: None,
+        pxWid: px_w,
+        pxHei: px_h,
+        tileGridSize: grid,
+        spacing: 0,
+        padding: 0,
+        tags: vec![],
+        tagsSourceEnumUid: Some(ENUM_UID),
+        enumTags: enum_tags,
+        customData: custom_data,
+        savedSelections: vec![],
+        cachedPixelData: None,
+    }
+}
+
+/// Builds LDtk definitions for the sheet, tagging every tile with the
+/// `Markers` enum so auto-layer rules can be driven by the markings.
+/// Sprite tags become `Tag_` values of the same enum. Every page of a
+/// multi-pack sheet gets its own tileset, named `identifier` and the page
+/// number.
+pub fn project_defs(
+    pages: &[SpriteSheetInfo],
+    marked: &[SpriteData],
+    identifier: &str,
+) -> LdtkDefs {
+    // Pages of a multi-pack share the scale, so they share the grid too.
+    let grid = grid_size(marked, &pages[0].meta);
+
+    let tag_ids = tag_value_ids(&all_tags(marked));
+    let mut ids = enum_value_ids();
+    ids.extend(tag_ids.iter().map(|&(_, ref id)| id.clone()));
+    let tag_ids: HashMap<String, String> = tag_ids.into_iter().collect();
+
+    let tilesets = pages
+        .iter()
+        .enumerate()
+        .map(|(page, info)| {
+            let sprites: Vec<&SpriteData> = marked.iter().filter(|sd| sd.page == page).collect();
+            let identifier = if pages.len() == 1 {
+                String::from(identifier)
+            } else {
+                format!("{}_{}", identifier, page)
+            };
+            let uid = TILESET_UID + page as i64;
+            tileset_def(info, &sprites, &ids, &tag_ids, grid, identifier, uid)
+        })
+        .collect();
+
+    let markers = EnumDef {
+        identifier: String::from("Markers"),
+        uid: ENUM_UID,
+        values: ids.iter()
+            .map(|id| EnumValue {
+                id: id.clone(),
+                tileRect: None,
+                color: color_of(id),
+            })
+            .collect(),
+        iconTilesetUid: None,
+        externalRelPath: None,
+        externalFileChecksum: None,
+        tags: vec![],
+    };
+
+    LdtkDefs {
+        enums: vec![markers],
+        tilesets,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colliding_tags_get_distinct_ids() {
+        let tags = vec![
+            String::from("foo-bar"),
+            String::from("foo_bar"),
+            String::from("foo bar"),
+            String::from("ice"),
+        ];
+        let ids: Vec<String> = tag_value_ids(&tags).into_iter().map(|(_, id)| id).collect();
+        assert_eq!(ids, vec!["Tag_foo_bar", "Tag_foo_bar_2", "Tag_foo_bar_3", "Tag_ice"]);
+    }
+}
@@ -0,0 +1,33 @@
+pub mod godot;
+pub mod ldtk;
+pub mod texture_packer;
+
+use marker::SpriteData;
+use sprite::geom;
+use sprite::texture_packer::SpriteSheetMeta;
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Frame of the sprite on the atlas in pixels, marked frames are in
+/// logical units.
+pub fn atlas_frame(sd: &SpriteData, meta: &SpriteSheetMeta) -> geom::Rect {
+    sd.frame.scaled(meta.scale_factor())
+}
+
+/// Largest cell size in atlas pixels that every frame of the sheet is aligned to.
+pub fn grid_size(marked: &[SpriteData], meta: &SpriteSheetMeta) -> u32 {
+    marked
+        .iter()
+        .flat_map(|sd| {
+            let f = atlas_frame(sd, meta);
+            vec![f.x as u32, f.y as u32, f.w as u32, f.h as u32]
+        })
+        .fold(0, gcd)
+        .max(1)
+}
@@ -0,0 +1,42 @@
+use serde_json::{Map, Value};
+
+use marker::SpriteData;
+
+/// Frame objects of a TexturePacker sheet by file name, for both the
+/// `JSON (Array)` and `JSON (Hash)` layouts.
+fn frames_mut(sheet: &mut Value) -> Vec<(String, &mut Map<String, Value>)> {
+    match sheet.get_mut("frames") {
+        Some(&mut Value::Array(ref mut frames)) => frames
+            .iter_mut()
+            .filter_map(|f| {
+                let name = f.get("filename")?.as_str()?.to_owned();
+                Some((name, f.as_object_mut()?))
+            })
+            .collect(),
+        Some(&mut Value::Object(ref mut frames)) => frames
+            .iter_mut()
+            .filter_map(|(name, f)| Some((name.clone(), f.as_object_mut()?)))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Writes the marked pivots into a parsed TexturePacker sheet, leaving
+/// everything else as the packer wrote it. Returns how many frames changed.
+pub fn apply_pivots(sheet: &mut Value, marked: &[SpriteData]) -> usize {
+    let mut changed = 0;
+    for (name, frame) in frames_mut(sheet) {
+        if let Some(sd) = marked.iter().find(|sd| sd.name == name) {
+            let mut pivot = Map::new();
+            pivot.insert(String::from("x"), Value::from(sd.pivot.x as f64));
+            pivot.insert(String::from("y"), Value::from(sd.pivot.y as f64));
+            let pivot = Value::Object(pivot);
+
+            if frame.get("pivot") != Some(&pivot) {
+                frame.insert(String::from("pivot"), pivot);
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
@@ -1,4 +1,4 @@
-#[cfg(windows)]
+#[cfg(all(windows, feature = "native-dialog"))]
 pub mod navigator {
     use nwg::Ui;
     use nwg::{Error, FileDialog, FileDialogT};
@@ -13,7 +13,7 @@ pub mod navigator {
 
             let app: Ui<usize> = Ui::new().expect("Failed to initialize the Ui");
 
-            let ext_filter = format!("Needed(*.png;*.json)|All(*.*)");
+            let ext_filter = format!("Needed(*.json;*.png;*.webp;*.tga;*.dds;*.bmp)|All(*.*)");
 
             FileNavigator::setup_ui(&app, ext_filter).unwrap();
 
@@ -53,7 +53,7 @@ pub mod navigator {
     }
 }
 
-#[cfg(unix)]
+#[cfg(all(unix, feature = "native-dialog"))]
 pub mod navigator {
     extern crate gtk;
     use gtk::*;
@@ -79,8 +79,9 @@ pub mod navigator {
             );
 
             let ff = FileFilter::new();
-            FileFilter::add_pattern(&ff, "*.png");
-            FileFilter::add_pattern(&ff, "*.json");
+            for pattern in &["*.json", "*.png", "*.webp", "*.tga", "*.dds", "*.bmp"] {
+                FileFilter::add_pattern(&ff, pattern);
+            }
             FileFilter::set_name(&ff, "spritesheet");
 
             let ff2 = FileFilter::new();
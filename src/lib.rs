@@ -1,12 +1,25 @@
 extern crate image;
 extern crate ggez;
 extern crate serde_json;
+extern crate ron;
+extern crate bincode;
+extern crate rusttype;
+extern crate serde;
 
 #[macro_use]
 extern crate serde_derive;
 
 mod marker;
 mod sprite;
+pub mod export;
+pub mod codegen;
+pub mod lint;
+pub mod coverage;
+pub mod contact_sheet;
+pub mod diff;
 
 pub use marker::*;
 pub use sprite::geom;
+pub use sprite::texture_packer;
+pub use sprite::{atlas, variants};
+pub use sprite::Loader;
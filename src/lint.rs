@@ -0,0 +1,383 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use marker::*;
+use sprite::texture_packer::SpriteSheetInfo;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// `MM` marked together with edge squares.
+    ContradictoryGround,
+    /// Ground without any squares.
+    EmptyGround,
+    /// Platform without any horizontal caps.
+    EmptyPlatform,
+    /// Two sprites of the same page share a name.
+    DuplicateName,
+    /// `index` doesn't match the position in the marked file or the meta.
+    IndexMismatch,
+    /// `name` or `frame` differ from the meta frame at `index`.
+    FrameMismatch,
+    /// A meta frame has no marked sprite.
+    MissingSprite,
+    /// Scale variants of the sheet, `@1x` and `@2x`, don't have the same frames.
+    VariantMismatch,
+    /// Nine-slice borders overlap, they are wider or taller than the frame.
+    NineSliceOverflow,
+    /// An animation frame refers to a sprite that isn't in the marked data.
+    MissingFrame,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Rule {
+    pub fn all() -> Vec<Rule> {
+        vec![
+            Rule::ContradictoryGround,
+            Rule::EmptyGround,
+            Rule::EmptyPlatform,
+            Rule::DuplicateName,
+            Rule::IndexMismatch,
+            Rule::FrameMismatch,
+            Rule::MissingSprite,
+            Rule::VariantMismatch,
+            Rule::NineSliceOverflow,
+            Rule::MissingFrame,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Rule::ContradictoryGround => "contradictory-ground",
+            Rule::EmptyGround => "empty-ground",
+            Rule::EmptyPlatform => "empty-platform",
+            Rule::DuplicateName => "duplicate-name",
+            Rule::IndexMismatch => "index-mismatch",
+            Rule::FrameMismatch => "frame-mismatch",
+            Rule::MissingSprite => "missing-sprite",
+            Rule::VariantMismatch => "variant-mismatch",
+            Rule::NineSliceOverflow => "nine-slice-overflow",
+            Rule::MissingFrame => "missing-frame",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::all().into_iter().find(|r| r.name() == name)
+    }
+
+    pub fn default_severity(&self) -> Severity {
+        match *self {
+            Rule::EmptyGround | Rule::MissingSprite => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// Severity of every rule, `None` turns the rule off.
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    levels: HashMap<Rule, Option<Severity>>,
+}
+
+impl Default for LintConfig {
+    fn default() -> LintConfig {
+        LintConfig {
+            levels: Rule::all()
+                .into_iter()
+                .map(|r| (r, Some(r.default_severity())))
+                .collect(),
+        }
+    }
+}
+
+impl LintConfig {
+    pub fn set(&mut self, rule: Rule, level: Option<Severity>) {
+        self.levels.insert(rule, level);
+    }
+
+    pub fn level(&self, rule: Rule) -> Option<Severity> {
+        self.levels.get(&rule).cloned().unwrap_or(None)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub rule: Rule,
+    pub severity: Severity,
+    /// Position of the offending sprite in the marked data, if there is one.
+    pub sprite: Option<usize>,
+    pub name: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{}[{}]: {}: {}",
+            level,
+            self.rule.name(),
+            self.name,
+            self.message
+        )
+    }
+}
+
+struct Collector<'a> {
+    config: &'a LintConfig,
+    problems: Vec<Problem>,
+}
+
+impl<'a> Collector<'a> {
+    fn report(&mut self, rule: Rule, sprite: Option<usize>, name: &str, message: String) {
+        if let Some(severity) = self.config.level(rule) {
+            self.problems.push(Problem {
+                rule,
+                severity,
+                sprite,
+                name: String::from(name),
+                message,
+            });
+        }
+    }
+}
+
+fn is_edge(s: &Square) -> bool {
+    match *s {
+        Square::LT
+        | Square::MT
+        | Square::RT
+        | Square::LM
+        | Square::RM
+        | Square::LB
+        | Square::MB
+        | Square::RB => true,
+        _ => false,
+    }
+}
+
+/// Checks marked data for contradictions, and against the sheet meta if given.
+/// Problems are sorted with errors first.
+pub fn lint(
+    marked: &[SpriteData],
+    info: Option<&SpriteSheetInfo>,
+    config: &LintConfig,
+) -> Vec<Problem> {
+    let mut c = Collector {
+        config,
+        problems: vec![],
+    };
+    let mut names: HashMap<(usize, &str), usize> = HashMap::new();
+
+    for (pos, sd) in marked.iter().enumerate() {
+        let name = sd.name.as_str();
+
+        match sd.markers {
+            SpriteType::Ground { ref square } => {
+                if square.is_empty() {
+                    c.report(
+                        Rule::EmptyGround,
+                        Some(pos),
+                        name,
+                        format!("ground has no squares"),
+                    );
+                } else if square.contains(&Square::MM) {
+                    let edges: Vec<String> = square
+                        .iter()
+                        .filter(|s| is_edge(s))
+                        .map(|s| format!("{:?}", s))
+                        .collect();
+                    if !edges.is_empty() {
+                        c.report(
+                            Rule::ContradictoryGround,
+                            Some(pos),
+                            name,
+                            format!("MM is marked together with {}", edges.join(", ")),
+                        );
+                    }
+                }
+            }
+            SpriteType::Platform { ref horizontal } if horizontal.is_empty() => {
+                c.report(
+                    Rule::EmptyPlatform,
+                    Some(pos),
+                    name,
+                    format!("platform has no caps"),
+                );
+            }
+            SpriteType::NineSlice { ref insets } if !insets.fits(&sd.frame) => {
+                c.report(
+                    Rule::NineSliceOverflow,
+                    Some(pos),
+                    name,
+                    format!(
+                        "borders l{} r{} t{} b{} don't fit the {}x{} frame",
+                        insets.left, insets.right, insets.top, insets.bottom, sd.frame.w, sd.frame.h
+                    ),
+                );
+            }
+            _ => (),
+        };
+
+        // Pages of a multi-pack sheet may reuse names.
+        if let Some(first) = names.insert((sd.page, name), pos) {
+            c.report(
+                Rule::DuplicateName,
+                Some(pos),
+                name,
+                format!("name is already used by sprite #{}", first),
+            );
+        }
+
+        if sd.index != pos {
+            c.report(
+                Rule::IndexMismatch,
+                Some(pos),
+                name,
+                format!("index {} is stored at position {}", sd.index, pos),
+            );
+        }
+
+        if let Some(info) = info {
+            match info.frames.get(sd.index) {
+                None => c.report(
+                    Rule::IndexMismatch,
+                    Some(pos),
+                    name,
+                    format!(
+                        "index {} is out of the meta's {} frames",
+                        sd.index,
+                        info.frames.len()
+                    ),
+                ),
+                Some(frame) => {
+                    if frame.filename != sd.name {
+                        c.report(
+                            Rule::FrameMismatch,
+                            Some(pos),
+                            name,
+                            format!("meta frame {} is named {}", sd.index, frame.filename),
+                        );
+                    } else if info.logical_frame(frame) != sd.frame {
+                        c.report(
+                            Rule::FrameMismatch,
+                            Some(pos),
+                            name,
+                            format!(
+                                "frame {:?} differs from meta {:?}",
+                                sd.frame,
+                                info.logical_frame(frame)
+                            ),
+                        );
+                    }
+                }
+            };
+        }
+    }
+
+    if let Some(info) = info {
+        let marked_names: HashSet<&str> = marked.iter().map(|sd| sd.name.as_str()).collect();
+        for frame in info.frames.iter() {
+            if !marked_names.contains(frame.filename.as_str()) {
+                c.report(
+                    Rule::MissingSprite,
+                    None,
+                    &frame.filename,
+                    format!("frame is not in the marked data"),
+                );
+            }
+        }
+    }
+
+    let mut problems = c.problems;
+    problems.sort_by(|a, b| b.severity.cmp(&a.severity));
+    problems
+}
+
+/// Checks that scale variants of a sheet, which share one marked file,
+/// have the same frames. `variants` are labelled sheets, the first is the
+/// one being edited.
+pub fn variants(variants: &[(String, SpriteSheetInfo)], config: &LintConfig) -> Vec<Problem> {
+    let mut c = Collector {
+        config,
+        problems: vec![],
+    };
+
+    if let Some(&(ref label, ref first)) = variants.first() {
+        for &(ref other_label, ref other) in variants.iter().skip(1) {
+            for frame in first.frames.iter() {
+                if !other.frames.iter().any(|f| f.filename == frame.filename) {
+                    let message = format!("frame is missing from {}", other_label);
+                    c.report(Rule::VariantMismatch, None, &frame.filename, message);
+                }
+            }
+            for frame in other.frames.iter() {
+                if !first.frames.iter().any(|f| f.filename == frame.filename) {
+                    let message = format!("frame of {} is missing from {}", other_label, label);
+                    c.report(Rule::VariantMismatch, None, &frame.filename, message);
+                }
+            }
+        }
+    }
+    c.problems
+}
+
+/// Checks that every animation frame names a marked sprite.
+pub fn animations(
+    animations: &[Animation],
+    marked: &[SpriteData],
+    config: &LintConfig,
+) -> Vec<Problem> {
+    let mut c = Collector {
+        config,
+        problems: vec![],
+    };
+
+    for animation in animations.iter() {
+        for (ix, frame) in animation.frames.iter().enumerate() {
+            if !marked.iter().any(|sd| sd.name == frame.sprite) {
+                let message = format!("frame {} uses unknown sprite {}", ix + 1, frame.sprite);
+                c.report(Rule::MissingFrame, None, &animation.name, message);
+            }
+        }
+    }
+    c.problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level_ground() -> Vec<SpriteData> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/level_ground-marked.json");
+        SpriteData::load_marked(path).unwrap()
+    }
+
+    fn duplicates(marked: &[SpriteData]) -> Vec<usize> {
+        lint(marked, None, &LintConfig::default())
+            .iter()
+            .filter(|p| p.rule == Rule::DuplicateName)
+            .filter_map(|p| p.sprite)
+            .collect()
+    }
+
+    #[test]
+    fn duplicate_names_are_per_page() {
+        let mut marked = level_ground();
+        let mut copy = marked[0].clone();
+        copy.index = marked.len();
+        marked.push(copy);
+        assert_eq!(duplicates(&marked), vec![marked.len() - 1]);
+
+        marked.last_mut().unwrap().page = 1;
+        assert!(duplicates(&marked).is_empty());
+    }
+}
@@ -2,11 +2,14 @@ extern crate ggez;
 extern crate image;
 extern crate serde;
 extern crate serde_json;
+extern crate ron;
+extern crate bincode;
+extern crate rusttype;
 
-#[cfg(windows)]
+#[cfg(all(windows, feature = "native-dialog"))]
 extern crate native_windows_gui as nwg;
 
-#[cfg(unix)]
+#[cfg(all(unix, feature = "native-dialog"))]
 extern crate gtk;
 
 #[macro_use]
@@ -16,68 +19,164 @@ mod sprite;
 mod file_navigator;
 mod marker;
 mod ui;
+mod export;
+mod codegen;
+mod lint;
+mod coverage;
+mod contact_sheet;
+mod diff;
+mod cli;
+mod session;
 
 use std::path::PathBuf;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::fs;
 use std::rc::Rc;
 use std::fs::File;
+use std::io::BufReader;
 
+#[cfg(feature = "native-dialog")]
 use file_navigator::navigator::FileNavigator;
-use sprite::{geom, Loader};
-use marker::SpriteData;
+use sprite::{geom, variants, Loader};
+use marker::{Animation, MarkedFormat, SaveOptions, SpriteData};
+use sprite::texture_packer::SpriteSheetInfo;
+use lint::{LintConfig, Problem};
+use session::{Session, State};
 use ui::*;
+use ui::widgets::{TextInput, Widget};
 
 use ggez::{event, graphics, timer, Context, GameResult};
 use ggez::graphics::*;
 use ggez::conf::Conf;
-use ggez::event::MouseState;
+use ggez::event::{Keycode, Mod, MouseState, LSHIFTMOD, RSHIFTMOD};
 
-fn marked_path(meta_path: &PathBuf) -> PathBuf {
+fn marked_path(meta_path: &PathBuf, format: MarkedFormat) -> PathBuf {
     let mut sp = meta_path.clone();
     let name = String::from(sp.file_name().unwrap().to_string_lossy());
     let mut split = name.split('.');
 
-    let ext = split.next_back().unwrap().clone();
+    split.next_back().unwrap();
+    let ext = format.extension();
     let name: String = split.fold(String::new(), |mut a, s| {
         a.push_str(s);
         a
     });
+    // Scale variants share the marked file, `level@2x.json` uses `level-marked.json`.
+    let name = variants::base_name(&name);
     let new_name: String = format!("{}-marked.{}", name, ext);
     sp.set_file_name(new_name);
 
     sp
 }
 
+/// Sidecar the editor autosaves into, next to the marked file.
+fn recovery_path(marked_path: &PathBuf) -> PathBuf {
+    let mut name = marked_path.file_name().unwrap().to_os_string();
+    name.push(".recovery");
+    marked_path.with_file_name(name)
+}
+
+/// Whether the recovery file holds changes newer than the marked file.
+fn recovery_is_newer(marked_path: &PathBuf) -> bool {
+    let modified = |p: &PathBuf| fs::metadata(p).and_then(|m| m.modified()).ok();
+
+    match (modified(&recovery_path(marked_path)), modified(marked_path)) {
+        (Some(recovery), Some(marked)) => recovery > marked,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// Marked file of the sheet in whichever format the project already uses.
+/// All pages of a multi-pack share the marked file of the first page.
+fn find_marked_path(meta_path: &PathBuf) -> PathBuf {
+    let first_page = Loader::page_paths(meta_path)
+        .ok()
+        .and_then(|pages| pages.into_iter().next())
+        .unwrap_or_else(|| meta_path.clone());
+
+    MarkedFormat::all()
+        .iter()
+        .map(|f| marked_path(&first_page, *f))
+        .find(|p| p.exists())
+        .unwrap_or_else(|| marked_path(&first_page, MarkedFormat::Json))
+}
+
+#[cfg(feature = "native-dialog")]
 fn check_ext(p: &PathBuf, ext: &str) -> bool {
     let sr = format!("{}", p.display());
     sr.ends_with(ext)
 }
 
-fn select_file() -> (PathBuf, PathBuf) {
+/// Sheet picked in the platform dialog. The meta is enough, the atlas is
+/// the one its `image` field names.
+#[cfg(feature = "native-dialog")]
+fn select_file() -> Option<(PathBuf, PathBuf)> {
     let selected = FileNavigator::select_files();
 
-    let meta = selected.iter().find(|p| check_ext(p, ".json")).unwrap();
-    let image = selected.iter().find(|p| check_ext(p, ".png")).unwrap();
-    (meta.clone(), image.clone())
+    let meta = selected.iter().find(|p| check_ext(p, ".json"));
+    meta.or(selected.first()).and_then(|path| ui::sheet_pair(path))
+}
+
+#[cfg(not(feature = "native-dialog"))]
+fn select_file() -> Option<(PathBuf, PathBuf)> {
+    None
 }
 
 pub fn main() {
-    let (meta, image) = select_file();
-    let gt = std::thread::spawn(move || { lets_play(&meta, &image); });
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
+    let mut options = match cli::open_options(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    if options.sheet.is_none() {
+        options.sheet = select_file();
+    }
+
+    let gt = std::thread::spawn(move || { lets_play(options); });
     gt.join().unwrap();
 }
 
-fn lets_play(meta: &PathBuf, image: &PathBuf) {
+/// Opens the window, showing the file browser first when no sheet was picked.
+fn lets_play(options: cli::OpenOptions) {
     let c = Conf {
         window_title: String::from("Jeopardy v0.1"),
-        window_height: 1000,
-        window_width: 1600,
+        window_height: options.height,
+        window_width: options.width,
         vsync: true,
         resizable: false,
         window_icon: String::from(""),
     };
     let ctx = &mut Context::load_from_conf("game", "ez", c).unwrap();
-    let mut state = Game::new(ctx, meta.clone(), image.clone()).unwrap();
+
+    let selected = match options.sheet {
+        Some(selected) => selected,
+        None => {
+            let mut picker = Picker::new(ctx).unwrap();
+            event::run(ctx, &mut picker).unwrap();
+            match picker.picked {
+                Some(picked) => picked,
+                None => return,
+            }
+        }
+    };
+
+    let (meta, image) = selected;
+    let marked = options.marked.unwrap_or_else(|| find_marked_path(&meta));
+    let mut state = match Game::with_marked(ctx, meta, image, marked) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
     event::run(ctx, &mut state).unwrap();
 }
 
@@ -94,58 +193,309 @@ impl Assets {
     }
 }
 
+const AUTOSAVE_INTERVAL: u64 = 30;
+/// Sprites per row of the grid and the size of their square cells.
+const GRID_COLUMNS: usize = 3;
+const GRID_CELL: f32 = 400.0;
+/// How close to the pivot crosshair, an anchor or a nine-slice guide the
+/// mouse has to be, in pixels.
+const SNAP_DISTANCE: f32 = 10.0;
+
+fn distance(a: &Point, b: &Point) -> f32 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+fn browser_center() -> Point {
+    Point::new(800.0, 500.0)
+}
+
+fn current_dir() -> PathBuf {
+    std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+}
+
+fn recent_sheets() -> Vec<(PathBuf, PathBuf)> {
+    State::load()
+        .recent()
+        .into_iter()
+        .map(|r| (r.meta, r.image))
+        .collect()
+}
+
+/// Shows only the file browser, for starting without a sheet.
+struct Picker {
+    browser: FileBrowser,
+    click: Option<Point>,
+    result: Option<BrowserResult>,
+    picked: Option<(PathBuf, PathBuf)>,
+}
+
+impl Picker {
+    fn new(ctx: &mut Context) -> GameResult<Picker> {
+        let assets = Rc::new(Assets::load(ctx)?);
+        let browser = FileBrowser::new(
+            ctx,
+            assets,
+            browser_center(),
+            current_dir(),
+            recent_sheets(),
+        )?;
+        Ok(Picker {
+            browser,
+            click: None,
+            result: None,
+            picked: None,
+        })
+    }
+
+    fn finish(&mut self, ctx: &mut Context, result: BrowserResult) -> GameResult<()> {
+        if let BrowserResult::Picked(meta, image) = result {
+            self.picked = Some((meta, image));
+        }
+        ctx.quit()
+    }
+}
+
+impl event::EventHandler for Picker {
+    fn update(&mut self, ctx: &mut Context, _dt: Duration) -> GameResult<()> {
+        let clicked = self.click
+            .take()
+            .and_then(|point| self.browser.interact(&point));
+        if let Some(result) = self.result.take().or(clicked) {
+            self.finish(ctx, result)?;
+        }
+        self.browser.update(ctx)
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx);
+        self.browser.draw(ctx)?;
+        graphics::present(ctx);
+        timer::sleep_until_next_frame(ctx, 60);
+        Ok(())
+    }
+
+    fn mouse_motion_event(&mut self, _state: MouseState, x: i32, y: i32, _xrel: i32, _yrel: i32) {
+        self.browser.hover(&Point::new(x as f32, y as f32));
+    }
+
+    fn mouse_button_down_event(&mut self, button: event::MouseButton, x: i32, y: i32) {
+        if button == event::MouseButton::Left {
+            self.click = Some(Point::new(x as f32, y as f32));
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, _x: i32, y: i32) {
+        self.browser.scroll(y);
+    }
+
+    fn key_down_event(&mut self, keycode: Keycode, keymod: Mod, _repeat: bool) {
+        let shift = keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+        self.result = self.browser.key(keycode, shift);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Prompt {
+    Restore,
+    Discard,
+    SaveAndQuit,
+    Quit,
+    Cancel,
+}
+
 pub struct Game {
+    meta_path: PathBuf,
+    image_path: PathBuf,
     marked_path: PathBuf,
+    animations_path: PathBuf,
     pub assets: Rc<Assets>,
     pub ui: AssetTypeUi,
     pub marked: Vec<SpriteData>,
     pub sprites_render: Vec<(DrawParam, usize, Rect)>,
     pub scroll: f32,
-    pub image: Rc<Image>,
+    /// Atlas of each page, indexed by `SpriteData::page`.
+    pub images: Vec<Rc<Image>>,
     pub selected: Option<(Rect, usize)>,
     pub hovered: Option<(Rect, usize)>,
     pub click: Option<Point>,
+    pub action: Option<UiAction>,
+    pub multi_selected: Vec<usize>,
+    pub info: SpriteSheetInfo,
+    pub problems: Vec<Problem>,
+    /// Frame differences to the sheet's other scale variants, checked on load.
+    variant_problems: Vec<Problem>,
+    pub problems_ui: ProblemsUi,
+    pub dialog: Option<Dialog<Prompt>>,
+    pub browser: Option<FileBrowser>,
+    browsed: Option<BrowserResult>,
+    pub animations: Vec<Animation>,
+    /// Animations as they are on disk, to tell whether there are changes.
+    saved_animations: Vec<Animation>,
+    animations_ui: Option<AnimationsUi>,
+    tags_ui: Option<TagsUi>,
+    /// Only sprites matching this are shown, see `SpriteData::matches`.
+    pub filter: String,
+    filter_input: TextInput<String>,
+    /// Selected once the render list has it, the grid may have just changed.
+    pending_select: Option<usize>,
+    needs_lint: bool,
+    dirty: bool,
+    last_autosave: Instant,
+    quit_requested: bool,
+    quitting: bool,
+    ctrl: bool,
+    dragging_pivot: bool,
+    dragging_guide: Option<Guide>,
 }
 
 impl Game {
     pub fn new(ctx: &mut Context, meta_path: PathBuf, image_path: PathBuf) -> GameResult<Game> {
+        let marked_path = find_marked_path(&meta_path);
+        Game::with_marked(ctx, meta_path, image_path, marked_path)
+    }
+
+    /// Edits the sheet's markers in `marked_path` instead of the file next to the meta.
+    pub fn with_marked(
+        ctx: &mut Context,
+        meta_path: PathBuf,
+        image_path: PathBuf,
+        marked_path: PathBuf,
+    ) -> GameResult<Game> {
         let sprite = Loader::load_sprite_sheet(ctx, &meta_path, &image_path)?;
         let assets = Rc::new(Assets::load(ctx)?);
-        let ui = AssetTypeUi::new(ctx, assets.clone(), Point::new(1400.0, 200.0), None)?;
+        let ui = AssetTypeUi::new(ctx, assets.clone(), Point::new(1400.0, 200.0), None, None)?;
+
+        let images = sprite.images.clone();
 
-        let image = sprite.image.clone();
+        let marked: Vec<SpriteData> = SpriteData::load_marked(&marked_path)
+            .unwrap_or_else(|_| SpriteData::create_pages(&sprite.pages));
+        let problems_ui = ProblemsUi::new(assets.clone(), Point::new(1400.0, 670.0), 380.0);
 
-        let marked_path = marked_path(&meta_path);
-        let marked: Vec<SpriteData> = if let Some(data) = File::open(marked_path.clone())
-            .ok()
-            .and_then(|f| serde_json::from_reader(f).ok())
-        {
-            data
+        let animations_path = marker::animations_path(&marked_path);
+        let animations = if animations_path.exists() {
+            marker::load_animations(&animations_path).unwrap_or_else(|e| {
+                println!("Could not load the animations: {}", e);
+                vec![]
+            })
         } else {
-            SpriteData::create(&sprite.info)
+            vec![]
         };
 
+        let dialog = if recovery_is_newer(&marked_path) {
+            Some(Dialog::new(
+                ctx,
+                assets.clone(),
+                "Unsaved changes from the last session were found, restore them?",
+                Point::new(800.0, 500.0),
+                vec![("Restore", Prompt::Restore), ("Discard", Prompt::Discard)],
+            )?)
+        } else {
+            None
+        };
+
+        let variant_problems = match variants::load_variants(&meta_path) {
+            Ok(variants) => lint::variants(&variants, &LintConfig::default()),
+            Err(e) => {
+                println!("Could not check the scale variants: {}", e);
+                vec![]
+            }
+        };
+
+        let mut state = State::load();
+        let session = state.session(&meta_path).cloned().unwrap_or_default();
+        state.opened(&meta_path, &image_path);
+        if let Err(e) = state.save() {
+            println!("Could not save the recent files: {}", e);
+        }
+
+        let mut filter_input = TextInput::new(
+            assets.clone(),
+            380.0,
+            "Filter by name or #tag",
+            Box::new(|value: &str| String::from(value)),
+        );
+        filter_input.set_value(&session.filter);
+        filter_input.layout(Point::new(1400.0, 40.0));
+
+        let pending_select = session
+            .selected
+            .as_ref()
+            .and_then(|name| marked.iter().find(|sd| sd.name == *name))
+            .map(|sd| sd.index);
+
         Ok(Game {
+            meta_path,
+            image_path,
             marked_path,
+            animations_path,
             ui,
             assets,
             marked,
             sprites_render: vec![],
-            scroll: 0.0,
-            image,
+            scroll: session.scroll,
+            images,
             selected: None,
             hovered: None,
             click: None,
+            action: None,
+            multi_selected: vec![],
+            info: sprite.info,
+            problems: vec![],
+            variant_problems,
+            problems_ui,
+            dialog,
+            browser: None,
+            browsed: None,
+            saved_animations: animations.clone(),
+            animations,
+            animations_ui: None,
+            tags_ui: None,
+            filter: session.filter,
+            filter_input,
+            pending_select,
+            needs_lint: true,
+            dirty: false,
+            last_autosave: Instant::now(),
+            quit_requested: false,
+            quitting: false,
+            ctrl: false,
+            dragging_pivot: false,
+            dragging_guide: None,
         })
     }
 
     pub fn hover(&mut self, x: i32, y: i32) {
         let point = Point::new(x as f32, y as f32);
 
+        if let Some(ref mut dialog) = self.dialog {
+            dialog.hover(&point);
+            return;
+        }
+
+        if let Some(ref mut browser) = self.browser {
+            browser.hover(&point);
+            return;
+        }
+
+        if let Some(ref mut panel) = self.animations_ui {
+            panel.hover(&point);
+            return;
+        }
+
+        if let Some(ref mut panel) = self.tags_ui {
+            if panel.contains(&point) {
+                panel.hover(&point);
+                self.hovered = None;
+                return;
+            }
+        }
+
         let dp = self.sprites_render
             .iter()
             .find(|tuple| ui::point_within(&point, &tuple.2));
 
+        self.filter_input.hover(&point);
+
         match dp {
             Some(&(_, ix, rect)) => {
                 let mut r = rect.clone();
@@ -154,6 +504,7 @@ impl Game {
             }
             None => {
                 self.ui.hover(&point);
+                self.problems_ui.hover(&point);
                 self.hovered = None
             }
         };
@@ -161,8 +512,13 @@ impl Game {
 
     pub fn unselect(&mut self) {
         if let Some((_, ix)) = self.selected {
-            self.marked[ix] = self.ui.full_state().unwrap();
+            let state = self.ui.full_state().unwrap();
+            if state != self.marked[ix] {
+                self.dirty = true;
+            }
+            self.marked[ix] = state;
             self.selected = None;
+            self.needs_lint = true;
         } else {
             panic!("Nothing is selected!")
         };
@@ -175,42 +531,526 @@ impl Game {
                 self.assets.clone(),
                 Point::new(1400.0, 200.0),
                 Some(&self.marked[ix]),
+                self.images.get(self.marked[ix].page).cloned(),
             ).unwrap();
-            self.selected = self.hovered.clone();
+            self.selected = self.sprite_rect(ix).map(|r| (r, ix));
             self.ui = ui;
         } else {
             panic!("Selected already!")
         };
     }
 
-    pub fn save(&mut self) {
+    /// On-screen rect of the sprite without scroll applied.
+    fn sprite_rect(&self, ix: usize) -> Option<Rect> {
+        self.sprites_render
+            .iter()
+            .find(|tuple| tuple.1 == ix)
+            .map(|&(_, _, rect)| {
+                let mut r = rect.clone();
+                r.y -= self.scroll;
+                r
+            })
+    }
+
+    /// Indices of the sprites passing the filter, in grid order.
+    fn visible(&self) -> Vec<usize> {
+        self.marked
+            .iter()
+            .filter(|sd| sd.matches(&self.filter))
+            .map(|sd| sd.index)
+            .collect()
+    }
+
+    /// Selects the sprite and scrolls its row to the top of the window,
+    /// clearing the filter if it hides the sprite.
+    pub fn focus(&mut self, ix: usize) {
         if self.selected.is_some() {
             self.unselect();
         }
+        if !self.visible().contains(&ix) {
+            self.set_filter(String::new());
+        }
+        let position = self.visible().iter().position(|&i| i == ix).unwrap_or(ix);
+        self.scroll = -((position / GRID_COLUMNS) as f32 * GRID_CELL);
+        self.pending_select = Some(ix);
+    }
 
-        let sp = &self.marked_path;
+    /// Changes the filter, keeping the selection if it stays visible.
+    fn set_filter(&mut self, filter: String) {
+        self.filter_input.set_value(&filter);
+        self.filter = filter;
+        self.scroll = 0.0;
+        if let Some((_, ix)) = self.selected {
+            self.unselect();
+            if self.visible().contains(&ix) {
+                self.pending_select = Some(ix);
+            }
+        }
+    }
 
-        let file = File::create(sp).unwrap();
-        serde_json::to_writer_pretty(file, &self.marked).unwrap();
+    fn session(&self) -> Session {
+        Session {
+            scroll: self.scroll,
+            selected: self.selected
+                .map(|(_, ix)| ix)
+                .or(self.pending_select)
+                .map(|ix| self.marked[ix].name.clone()),
+            filter: self.filter.clone(),
+        }
+    }
+
+    /// Stores where the user is in this sheet for the next time it's opened.
+    fn remember(&self) {
+        let mut state = State::load();
+        state.set_session(&self.meta_path, self.session());
+        state.opened(&self.meta_path, &self.image_path);
+        if let Err(e) = state.save() {
+            println!("Could not save the session: {}", e);
+        }
+    }
+
+    /// Trimmed rect within the source and the source size of a sprite,
+    /// the pivot is relative to the source.
+    fn trim(&self, ix: usize) -> Option<(geom::Rect, geom::Size)> {
+        self.info
+            .frames
+            .get(ix)
+            .filter(|f| f.filename == self.marked[ix].name)
+            .filter(|f| f.spriteSourceSize.w > 0.0 && f.spriteSourceSize.h > 0.0)
+            .map(|f| (f.spriteSourceSize.clone(), f.sourceSize.clone()))
+    }
+
+    /// Where a pivot of the selected sprite is on screen.
+    fn pivot_on_screen(&self, pivot: &geom::Point) -> Option<Point> {
+        let (rect, ix) = self.selected?;
+        let r = rect_with_scroll(&rect, self.scroll);
+        let (fx, fy) = match self.trim(ix) {
+            Some((t, s)) => ((pivot.x * s.w - t.x) / t.w, (pivot.y * s.h - t.y) / t.h),
+            None => (pivot.x, pivot.y),
+        };
+        Some(Point::new(
+            r.x - r.w / 2.0 + fx * r.w,
+            r.y - r.h / 2.0 + fy * r.h,
+        ))
+    }
+
+    /// Pivot of the selected sprite under a screen point, snapped to an
+    /// anchor within `SNAP_DISTANCE`.
+    fn pivot_at(&self, point: &Point) -> Option<geom::Point> {
+        let (rect, ix) = self.selected?;
+        let snapped = Anchor::all().iter().map(|a| a.pivot()).find(|p| {
+            self.pivot_on_screen(p)
+                .map(|s| distance(&s, point) < SNAP_DISTANCE)
+                .unwrap_or(false)
+        });
+        if snapped.is_some() {
+            return snapped;
+        }
+
+        let r = rect_with_scroll(&rect, self.scroll);
+        let fx = (point.x - (r.x - r.w / 2.0)) / r.w;
+        let fy = (point.y - (r.y - r.h / 2.0)) / r.h;
+        Some(match self.trim(ix) {
+            Some((t, s)) => geom::Point {
+                x: (fx * t.w + t.x) / s.w,
+                y: (fy * t.h + t.y) / s.h,
+            },
+            None => geom::Point { x: fx, y: fy },
+        })
+    }
+
+    /// Nine-slice guides of the selected sprite and their screen position,
+    /// x for vertical guides and y for horizontal ones.
+    fn guides(&self) -> Vec<(Guide, f32)> {
+        match (self.selected, self.ui.insets()) {
+            (Some((rect, ix)), Some(insets)) => {
+                let r = rect_with_scroll(&rect, self.scroll);
+                let frame = &self.marked[ix].frame;
+                Guide::all()
+                    .iter()
+                    .map(|g| (*g, g.position(&insets, frame, &r)))
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Guide within `SNAP_DISTANCE` of a point over the selected sprite.
+    fn guide_at(&self, point: &Point) -> Option<Guide> {
+        let (rect, _) = self.selected?;
+        let mut r = rect_with_scroll(&rect, self.scroll);
+        r.w += SNAP_DISTANCE * 2.0;
+        r.h += SNAP_DISTANCE * 2.0;
+        if !ui::point_within(point, &r) {
+            return None;
+        }
+
+        self.guides()
+            .into_iter()
+            .find(|&(guide, position)| {
+                let along = if guide.is_vertical() { point.x } else { point.y };
+                (along - position).abs() < SNAP_DISTANCE
+            })
+            .map(|(guide, _)| guide)
+    }
+
+    fn drag_guide(&mut self, guide: Guide, point: &Point) {
+        if let (Some((rect, ix)), Some(insets)) = (self.selected, self.ui.insets()) {
+            let r = rect_with_scroll(&rect, self.scroll);
+            let moved = guide.moved(&insets, &self.marked[ix].frame, &r, point);
+            self.ui.set_insets(moved);
+        }
+    }
+
+    fn relint(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.problems = lint::lint(&self.marked, Some(&self.info), &LintConfig::default());
+        self.problems.extend(self.variant_problems.iter().cloned());
+        let config = LintConfig::default();
+        self.problems.extend(lint::animations(&self.animations, &self.marked, &config));
+        self.problems.sort_by(|a, b| b.severity.cmp(&a.severity));
+        self.problems_ui.update(ctx, &self.problems)?;
+        self.needs_lint = false;
+        Ok(())
+    }
+
+    /// Marked data including the edits of the currently selected sprite.
+    fn snapshot(&self) -> Vec<SpriteData> {
+        let mut marked = self.marked.clone();
+        if let Some((_, ix)) = self.selected {
+            if let Some(state) = self.ui.full_state() {
+                marked[ix] = state;
+            }
+        }
+        marked
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.dirty || self.snapshot() != self.marked || self.animations != self.saved_animations
+    }
+
+    fn marked_format(&self) -> MarkedFormat {
+        MarkedFormat::from_path(&self.marked_path).unwrap_or(MarkedFormat::Json)
+    }
+
+    fn autosave(&mut self) -> GameResult<()> {
+        let options = SaveOptions {
+            format: Some(self.marked_format()),
+            backups: 0,
+        };
+        marker::save_marked(recovery_path(&self.marked_path), &self.snapshot(), &options)?;
+        if self.animations != self.saved_animations {
+            let path = recovery_path(&self.animations_path);
+            marker::save_animations(path, &self.animations, &options)?;
+        }
+        self.last_autosave = Instant::now();
+        Ok(())
+    }
+
+    fn restore(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let file = File::open(recovery_path(&self.marked_path))?;
+        self.marked = marker::read_marked(BufReader::new(file), self.marked_format())?;
+        // The panel still holds the markers from before the recovery.
+        match self.selected {
+            Some((_, ix)) if ix < self.marked.len() => {
+                self.ui = AssetTypeUi::new(
+                    ctx,
+                    self.assets.clone(),
+                    Point::new(1400.0, 200.0),
+                    Some(&self.marked[ix]),
+                    self.images.get(self.marked[ix].page).cloned(),
+                )?;
+            }
+            _ => self.selected = None,
+        }
+        let animations = recovery_path(&self.animations_path);
+        if animations.exists() {
+            self.animations = marker::load_animations(animations)?;
+        }
+        self.dirty = true;
+        self.needs_lint = true;
+        Ok(())
+    }
+
+    fn discard_recovery(&self) {
+        let _ = fs::remove_file(recovery_path(&self.marked_path));
+        let _ = fs::remove_file(recovery_path(&self.animations_path));
+    }
+
+    fn answer(&mut self, ctx: &mut Context, prompt: Prompt) -> GameResult<()> {
+        match prompt {
+            Prompt::Restore => self.restore(ctx)?,
+            Prompt::Discard => self.discard_recovery(),
+            Prompt::SaveAndQuit => {
+                self.save()?;
+                self.quitting = true;
+                ctx.quit()?;
+            }
+            Prompt::Quit => {
+                self.discard_recovery();
+                self.quitting = true;
+                ctx.quit()?;
+            }
+            Prompt::Cancel => (),
+        };
+        Ok(())
+    }
+
+    /// Switches to another sheet. Unsaved edits go to the recovery file, so
+    /// coming back to this sheet offers to restore them.
+    fn open(&mut self, ctx: &mut Context, meta: PathBuf, image: PathBuf) -> GameResult<()> {
+        if self.has_changes() {
+            // Stay on this sheet rather than lose the edits.
+            if let Err(e) = self.autosave() {
+                self.dialog = Some(Dialog::new(
+                    ctx,
+                    self.assets.clone(),
+                    &format!("Could not keep the unsaved changes: {}", e),
+                    Point::new(800.0, 500.0),
+                    vec![("OK", Prompt::Cancel)],
+                )?);
+                return Ok(());
+            }
+        }
+        self.remember();
+
+        match Game::new(ctx, meta, image) {
+            Ok(game) => *self = game,
+            Err(e) => {
+                self.dialog = Some(Dialog::new(
+                    ctx,
+                    self.assets.clone(),
+                    &format!("Could not open the sheet: {}", e),
+                    Point::new(800.0, 500.0),
+                    vec![("OK", Prompt::Cancel)],
+                )?);
+            }
+        };
+        Ok(())
+    }
+
+    pub fn toggle_multi_selected(&mut self, ix: usize) {
+        if self.multi_selected.contains(&ix) {
+            self.multi_selected.retain(|&i| i != ix);
+        } else {
+            self.multi_selected.push(ix);
+        }
+    }
+
+    /// Sprites the tag editor works on, the multi-selection or else the
+    /// selected sprite.
+    fn tag_targets(&self) -> Vec<usize> {
+        if self.multi_selected.is_empty() {
+            self.selected.map(|(_, ix)| vec![ix]).unwrap_or_default()
+        } else {
+            self.multi_selected.clone()
+        }
+    }
+
+    /// Changes the tags of the sprites, keeping the selected one's panel in step.
+    fn retag<F: Fn(&mut SpriteData) -> bool>(&mut self, targets: &[usize], change: F) {
+        let selected = self.selected.map(|(_, ix)| ix);
+        for &ix in targets.iter() {
+            if change(&mut self.marked[ix]) {
+                self.dirty = true;
+                if selected == Some(ix) {
+                    self.ui.set_tags(self.marked[ix].tags.clone());
+                }
+            }
+        }
+    }
+
+    fn apply_tags(&mut self, msg: TagsMsg) {
+        let targets = self.tag_targets();
+        match msg {
+            TagsMsg::Add(tag) => self.retag(&targets, |sd| sd.add_tag(&tag)),
+            TagsMsg::Toggle(tag) => {
+                if targets.iter().all(|&ix| self.marked[ix].has_tag(&tag)) {
+                    self.retag(&targets, |sd| sd.remove_tag(&tag));
+                } else {
+                    self.retag(&targets, |sd| sd.add_tag(&tag));
+                }
+            }
+            TagsMsg::Filter(tag) => {
+                // Showing the tag that is already filtered shows everything again.
+                let filter = format!("#{}", tag);
+                if self.filter == filter {
+                    self.set_filter(String::new());
+                } else {
+                    self.set_filter(filter);
+                }
+            }
+            TagsMsg::Close => self.tags_ui = None,
+        }
+    }
+
+    fn perform(&mut self, ctx: &mut Context, action: UiAction) -> GameResult<()> {
+        match action {
+            UiAction::Save => self.save()?,
+            UiAction::Revert => if let Some((rect, ix)) = self.selected {
+                self.ui = AssetTypeUi::new(
+                    ctx,
+                    self.assets.clone(),
+                    Point::new(1400.0, 200.0),
+                    Some(&self.marked[ix]),
+                    self.images.get(self.marked[ix].page).cloned(),
+                )?;
+                self.selected = Some((rect, ix));
+            },
+            UiAction::Next | UiAction::Previous => {
+                let visible = self.visible();
+                let current = self.selected
+                    .and_then(|(_, ix)| visible.iter().position(|&i| i == ix));
+                let target = match (action, current) {
+                    (UiAction::Next, Some(pos)) => (pos + 1).min(visible.len() - 1),
+                    (UiAction::Previous, Some(pos)) => pos.saturating_sub(1),
+                    _ => 0,
+                };
+                if let Some(&ix) = visible.get(target) {
+                    self.focus(ix);
+                }
+            }
+            UiAction::Open => {
+                let dir = self.marked_path
+                    .parent()
+                    .map(|p| p.to_path_buf())
+                    .unwrap_or_else(current_dir);
+                let browser = FileBrowser::new(
+                    ctx,
+                    self.assets.clone(),
+                    browser_center(),
+                    dir,
+                    recent_sheets(),
+                )?;
+                self.browser = Some(browser);
+            }
+            UiAction::Animations => {
+                let panel = AnimationsUi::new(ctx, self.assets.clone(), &self.animations)?;
+                self.animations_ui = Some(panel);
+            }
+            UiAction::Tags => {
+                let targets = self.tag_targets();
+                let panel = TagsUi::new(ctx, self.assets.clone(), &self.marked, &targets)?;
+                self.tags_ui = Some(panel);
+            }
+            UiAction::ApplyToSelection => if let Some(markers) = self.ui.return_state() {
+                for &ix in self.multi_selected.iter() {
+                    if self.marked[ix].markers != markers {
+                        self.marked[ix].markers = markers.clone();
+                        self.dirty = true;
+                    }
+                }
+                self.needs_lint = true;
+            },
+        };
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> GameResult<()> {
+        if let Some((_, ix)) = self.selected {
+            self.unselect();
+            self.pending_select = Some(ix);
+        }
+
+        marker::save_marked(&self.marked_path, &self.marked, &SaveOptions::default())?;
+        // Sheets without animations don't get an empty file.
+        if !self.animations.is_empty() || self.animations_path.exists() {
+            let options = SaveOptions::default();
+            marker::save_animations(&self.animations_path, &self.animations, &options)?;
+        }
+        self.saved_animations = self.animations.clone();
+        self.dirty = false;
+        self.discard_recovery();
+        Ok(())
     }
 }
 
 impl event::EventHandler for Game {
     fn update(&mut self, ctx: &mut Context, _dt: Duration) -> GameResult<()> {
-        let mut save_now = false;
+        let mut action = self.action.take();
+
+        if self.quit_requested && self.dialog.is_none() {
+            self.dialog = Some(Dialog::new(
+                ctx,
+                self.assets.clone(),
+                "There are unsaved changes.",
+                Point::new(800.0, 500.0),
+                vec![
+                    ("Save & quit", Prompt::SaveAndQuit),
+                    ("Quit", Prompt::Quit),
+                    ("Cancel", Prompt::Cancel),
+                ],
+            )?);
+        }
+        self.quit_requested = false;
+
+        if self.dialog.is_some() {
+            action = None;
+            let answer = self.click
+                .take()
+                .and_then(|point| self.dialog.as_mut().unwrap().interact(&point));
+            if let Some(prompt) = answer {
+                self.dialog = None;
+                self.answer(ctx, prompt)?;
+            }
+        }
+
+        if self.browser.is_some() {
+            action = None;
+            let clicked = self.click
+                .take()
+                .and_then(|point| self.browser.as_mut().unwrap().interact(&point));
+            match self.browsed.take().or(clicked) {
+                Some(BrowserResult::Picked(meta, image)) => {
+                    self.browser = None;
+                    return self.open(ctx, meta, image);
+                }
+                Some(BrowserResult::Cancelled) => self.browser = None,
+                None => self.browser.as_mut().unwrap().update(ctx)?,
+            }
+        }
+
+        if self.animations_ui.is_some() {
+            action = None;
+            let closed = match self.click.take() {
+                Some(point) => {
+                    self.needs_lint = true;
+                    self.animations_ui.as_mut().unwrap().interact(
+                        &point,
+                        &mut self.animations,
+                        &self.marked,
+                        &self.multi_selected,
+                    )
+                }
+                None => false,
+            };
+            if closed {
+                self.animations_ui = None;
+            } else if let Some(ref mut panel) = self.animations_ui {
+                panel.update(ctx, &self.animations)?;
+            }
+        }
 
         if let Some(ref point) = self.click.map(|c| c.clone()) {
-            if let Some(sel) = self.selected {
+            self.filter_input.click(point);
+            if ui::point_within(point, &self.filter_input.rect()) {
+                // Focused the filter, typing goes there now.
+            } else if self.tags_ui.as_ref().map(|p| p.contains(point)).unwrap_or(false) {
+                let msg = self.tags_ui.as_mut().and_then(|p| p.interact(point));
+                if let Some(msg) = msg {
+                    self.apply_tags(msg);
+                }
+            } else if let Some(ix) = self.problems_ui.interact(point) {
+                self.focus(ix);
+            } else if let (true, Some(hovered)) = (self.ctrl, self.hovered) {
+                self.toggle_multi_selected(hovered.1);
+            } else if let Some(sel) = self.selected {
                 if ui::point_within(&point, &rect_with_scroll(&sel.0, self.scroll)) {
                     self.unselect();
                 } else if let Some(hovered) = self.hovered {
                     self.unselect();
                     self.select(hovered.1, ctx);
                 } else {
-                    let opt = self.ui.interact(ctx, point).err();
-                    if let Some(ggez::GameError::UnknownError(_)) = opt {
-                        save_now = true;
-                    };
+                    action = self.ui.interact(ctx, point)?.or(action);
                 };
             } else if let Some(hovered) = self.hovered {
                 self.select(hovered.1, ctx);
@@ -218,20 +1058,40 @@ impl event::EventHandler for Game {
         };
         self.click = None;
 
-        if save_now {
-            self.save();
+        if let Some(action) = action {
+            self.perform(ctx, action)?;
+        };
+
+        let targets = self.tag_targets();
+        if let Some(ref mut panel) = self.tags_ui {
+            panel.update(ctx, &self.marked, &targets)?;
+        }
+
+        if self.needs_lint {
+            self.relint(ctx)?;
+        };
+
+        let due = self.last_autosave.elapsed() >= Duration::from_secs(AUTOSAVE_INTERVAL);
+        if due && self.has_changes() {
+            // A full disk shouldn't close the editor, the work is still in memory.
+            if let Err(e) = self.autosave() {
+                println!("Could not autosave: {}", e);
+                self.last_autosave = Instant::now();
+            }
         };
 
         self.sprites_render.clear();
-        for frame in self.marked.iter() {
+        let filter = &self.filter;
+        let visible = self.marked.iter().filter(|sd| sd.matches(filter));
+        for (position, frame) in visible.enumerate() {
             let ix = frame.index;
 
-            let x = ix % 3;
-            let y = ix / 3;
+            let x = position % GRID_COLUMNS;
+            let y = position / GRID_COLUMNS;
             let src = Rect::from(frame.on_screen_frame.clone());
             let dest = Point {
-                x: 200.0 + x as f32 * 400.0,
-                y: (200.0 + y as f32 * 400.0 + self.scroll),
+                x: GRID_CELL / 2.0 + x as f32 * GRID_CELL,
+                y: (GRID_CELL / 2.0 + y as f32 * GRID_CELL + self.scroll),
             };
             let geom::Rect {
                 x: _,
@@ -240,7 +1100,8 @@ impl event::EventHandler for Game {
                 h: orig_h,
             } = frame.frame;
             let max = orig_w.max(orig_h);
-            let scale = Point::new(380.0 / max, 380.0 / max);
+            let fit = GRID_CELL - 20.0;
+            let scale = Point::new(fit / max, fit / max);
             let param = DrawParam {
                 src,
                 dest,
@@ -256,14 +1117,25 @@ impl event::EventHandler for Game {
             };
             self.sprites_render.push((param, ix, on_screen_coordinates));
         }
+
+        // Wait for the answer, restoring replaces the markers to select from.
+        if self.dialog.is_none() {
+            if let Some(ix) = self.pending_select.take() {
+                if self.selected.is_none() && self.sprite_rect(ix).is_some() {
+                    self.select(ix, ctx);
+                }
+            }
+        }
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         graphics::clear(ctx);
 
-        for &(params, _, _) in self.sprites_render.iter() {
-            graphics::draw_ex(ctx, &*self.image, params.clone())?;
+        for &(params, ix, _) in self.sprites_render.iter() {
+            if let Some(image) = self.images.get(self.marked[ix].page) {
+                graphics::draw_ex(ctx, &**image, params.clone())?;
+            }
         }
 
         if let Some(hover) = self.hovered {
@@ -274,6 +1146,16 @@ impl event::EventHandler for Game {
             )?;
         }
 
+        for &ix in self.multi_selected.iter() {
+            if let Some(rect) = self.sprite_rect(ix) {
+                ui::draw_rect_with_outline(
+                    ctx,
+                    Color::new(1.0, 0.8, 0.0, 1.0),
+                    &rect_with_scroll(&rect, self.scroll),
+                )?;
+            }
+        }
+
         if let Some(selection) = self.selected {
             ui::draw_rect_with_outline(
                 ctx,
@@ -282,8 +1164,48 @@ impl event::EventHandler for Game {
             )?;
         }
 
-        if self.selected.is_some() {
-            self.ui.draw(ctx);
+        if let Some((rect, _)) = self.selected {
+            let r = rect_with_scroll(&rect, self.scroll);
+            graphics::set_color(ctx, Color::new(0.2, 0.9, 1.0, 1.0))?;
+            for (guide, position) in self.guides() {
+                let line = if guide.is_vertical() {
+                    Rect::new(position, r.y, 2.0, r.h)
+                } else {
+                    Rect::new(r.x, position, r.w, 2.0)
+                };
+                graphics::rectangle(ctx, DrawMode::Fill, line)?;
+            }
+            graphics::set_color(ctx, graphics::WHITE)?;
+        }
+
+        if let Some(pivot) = self.ui.pivot().and_then(|p| self.pivot_on_screen(&p)) {
+            graphics::set_color(ctx, Color::new(1.0, 0.2, 0.8, 1.0))?;
+            graphics::rectangle(ctx, DrawMode::Fill, Rect::new(pivot.x, pivot.y, 24.0, 2.0))?;
+            graphics::rectangle(ctx, DrawMode::Fill, Rect::new(pivot.x, pivot.y, 2.0, 24.0))?;
+            graphics::rectangle(ctx, DrawMode::Line, Rect::new(pivot.x, pivot.y, 10.0, 10.0))?;
+            graphics::set_color(ctx, graphics::WHITE)?;
+        }
+
+        if let Some(ref panel) = self.tags_ui {
+            panel.draw(ctx)?;
+        } else {
+            if self.selected.is_some() {
+                self.ui.draw(ctx);
+            };
+            self.problems_ui.draw(ctx)?;
+        }
+        self.filter_input.draw(ctx)?;
+
+        if let Some(ref panel) = self.animations_ui {
+            panel.draw(ctx, &self.animations, &self.marked, &self.images)?;
+        };
+
+        if let Some(ref browser) = self.browser {
+            browser.draw(ctx)?;
+        };
+
+        if let Some(ref dialog) = self.dialog {
+            dialog.draw(ctx)?;
         };
 
         graphics::present(ctx);
@@ -292,18 +1214,130 @@ impl event::EventHandler for Game {
     }
 
     fn mouse_motion_event(&mut self, state: MouseState, x: i32, y: i32, _xrel: i32, _yrel: i32) {
-        if !state.left() && !state.right() {
+        let point = Point::new(x as f32, y as f32);
+        if self.dragging_pivot && state.left() {
+            if let Some(pivot) = self.pivot_at(&point) {
+                self.ui.set_pivot(pivot);
+            }
+        } else if let (Some(guide), true) = (self.dragging_guide, state.left()) {
+            self.drag_guide(guide, &point);
+        } else if !state.left() && !state.right() {
             self.hover(x, y);
         }
     }
 
+    fn key_down_event(&mut self, keycode: Keycode, keymod: Mod, _repeat: bool) {
+        if let Some(ref mut browser) = self.browser {
+            let shift = keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+            self.browsed = browser.key(keycode, shift);
+            return;
+        }
+
+        let closed = match self.animations_ui {
+            Some(ref mut panel) => {
+                let shift = keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+                Some(panel.key(keycode, shift, &mut self.animations, &self.marked))
+            }
+            None => None,
+        };
+        if let Some(closed) = closed {
+            if closed {
+                self.animations_ui = None;
+            }
+            self.needs_lint = true;
+            return;
+        }
+
+        if self.filter_input.is_focused() {
+            let shift = keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+            if let Some(filter) = self.filter_input.key(keycode, shift) {
+                self.set_filter(filter);
+            }
+            return;
+        }
+
+        let tags_msg = match self.tags_ui {
+            Some(ref mut panel) if panel.is_focused() || keycode == Keycode::Escape => {
+                let shift = keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+                Some(panel.key(keycode, shift))
+            }
+            _ => None,
+        };
+        if let Some(msg) = tags_msg {
+            if let Some(msg) = msg {
+                self.apply_tags(msg);
+            }
+            return;
+        }
+
+        match keycode {
+            Keycode::LCtrl | Keycode::RCtrl => self.ctrl = true,
+            Keycode::S if self.ctrl => self.action = Some(UiAction::Save),
+            Keycode::O if self.ctrl => self.action = Some(UiAction::Open),
+            Keycode::A if self.ctrl => self.action = Some(UiAction::Animations),
+            Keycode::T if self.ctrl => self.action = Some(UiAction::Tags),
+            Keycode::PageDown => self.action = Some(UiAction::Next),
+            Keycode::PageUp => self.action = Some(UiAction::Previous),
+            _ => (),
+        }
+    }
+
+    fn key_up_event(&mut self, keycode: Keycode, _keymod: Mod, _repeat: bool) {
+        match keycode {
+            Keycode::LCtrl | Keycode::RCtrl => self.ctrl = false,
+            _ => (),
+        }
+    }
+
     fn mouse_button_down_event(&mut self, button: event::MouseButton, x: i32, y: i32) {
         if button == event::MouseButton::Left {
-            self.click = Some(Point::new(x as f32, y as f32));
+            let point = Point::new(x as f32, y as f32);
+            let modal =
+                self.dialog.is_some() || self.browser.is_some() || self.animations_ui.is_some();
+            let on_pivot = self.ui
+                .pivot()
+                .and_then(|p| self.pivot_on_screen(&p))
+                .map(|p| distance(&p, &point) < SNAP_DISTANCE)
+                .unwrap_or(false);
+
+            let guide = if modal { None } else { self.guide_at(&point) };
+
+            if on_pivot && !modal {
+                self.dragging_pivot = true;
+            } else if guide.is_some() {
+                self.dragging_guide = guide;
+            } else {
+                self.click = Some(point);
+            }
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, button: event::MouseButton, _x: i32, _y: i32) {
+        if button == event::MouseButton::Left {
+            self.dragging_pivot = false;
+            self.dragging_guide = None;
+        }
+    }
+
+    fn quit_event(&mut self) -> bool {
+        if self.quitting || !self.has_changes() {
+            self.remember();
+            false
+        } else {
+            self.quit_requested = true;
+            true
         }
     }
 
     fn mouse_wheel_event(&mut self, _x: i32, y: i32) {
+        if let Some(ref mut browser) = self.browser {
+            browser.scroll(y);
+            return;
+        }
+        if self.animations_ui.is_some() {
+            return;
+        }
+
         //1 up, -1 down
         let new_scroll = self.scroll + (y as f32 * 30.0);
         if new_scroll < 0.0 {
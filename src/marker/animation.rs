@@ -0,0 +1,287 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use ggez::GameResult;
+
+use super::*;
+
+/// Frame duration given to new animations, in milliseconds.
+pub const DEFAULT_FRAME_DURATION: u32 = 100;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum LoopMode {
+    Loop,
+    /// Plays once and stays on the last frame.
+    Once,
+    /// Plays forward, then backward, and again.
+    PingPong,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AnimationFrame {
+    /// Name of the sprite, like `SpriteData::name`.
+    pub sprite: String,
+    /// How long the frame is shown, in milliseconds.
+    pub duration: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Animation {
+    pub name: String,
+    pub frames: Vec<AnimationFrame>,
+    pub loop_mode: LoopMode,
+}
+
+impl Animation {
+    /// Looping animation of the sprites with the default duration.
+    pub fn new(name: &str, sprites: Vec<String>) -> Animation {
+        Animation {
+            name: String::from(name),
+            frames: sprites
+                .into_iter()
+                .map(|sprite| AnimationFrame {
+                    sprite,
+                    duration: DEFAULT_FRAME_DURATION,
+                })
+                .collect(),
+            loop_mode: LoopMode::Loop,
+        }
+    }
+
+    /// Frame indices in playing order for one pass, ping-pong doesn't
+    /// repeat the frames it turns around on.
+    fn timeline(&self) -> Vec<usize> {
+        let n = self.frames.len();
+        let mut timeline: Vec<usize> = (0..n).collect();
+        if self.loop_mode == LoopMode::PingPong && n > 2 {
+            timeline.extend((1..n - 1).rev());
+        }
+        timeline
+    }
+
+    /// Length of one pass in milliseconds.
+    pub fn duration(&self) -> u32 {
+        self.timeline()
+            .into_iter()
+            .map(|ix| self.frames[ix].duration)
+            .sum()
+    }
+
+    /// Index of the frame shown `elapsed` milliseconds after the start.
+    pub fn frame_at(&self, elapsed: u32) -> Option<usize> {
+        let timeline = self.timeline();
+        let total = self.duration();
+        if timeline.is_empty() {
+            return None;
+        }
+        if total == 0 || (self.loop_mode == LoopMode::Once && elapsed >= total) {
+            return timeline.last().cloned();
+        }
+
+        let mut t = elapsed % total;
+        for ix in timeline {
+            let duration = self.frames[ix].duration;
+            if t < duration {
+                return Some(ix);
+            }
+            t -= duration;
+        }
+        None
+    }
+}
+
+/// Splits a frame name into the sequence it belongs to and its number,
+/// `Bone (2).png` is frame 2 of `Bone`, so is `Bone_02.png`.
+pub fn sequence_name(name: &str) -> Option<(String, u32)> {
+    let stem = match name.rfind('.') {
+        Some(ix) if ix > 0 => &name[..ix],
+        _ => name,
+    };
+    let stem = stem.trim_end_matches(')');
+    let base = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = stem[base.len()..].parse::<u32>().ok()?;
+
+    let base = base.trim_end_matches(|c: char| " _-(/".contains(c));
+    if base.is_empty() {
+        None
+    } else {
+        Some((String::from(base), number))
+    }
+}
+
+/// One animation per numbered sequence of at least two sprites, frames in
+/// number order and sequences in the order they appear in the sheet.
+pub fn auto_group(marked: &[SpriteData]) -> Vec<Animation> {
+    let mut sequences: Vec<(String, Vec<(u32, String)>)> = vec![];
+    for sd in marked.iter() {
+        if let Some((base, number)) = sequence_name(&sd.name) {
+            match sequences.iter().position(|s| s.0 == base) {
+                Some(ix) => sequences[ix].1.push((number, sd.name.clone())),
+                None => sequences.push((base, vec![(number, sd.name.clone())])),
+            }
+        }
+    }
+
+    sequences
+        .into_iter()
+        .filter(|s| s.1.len() > 1)
+        .map(|(base, mut frames)| {
+            frames.sort_by_key(|f| f.0);
+            Animation::new(&base, frames.into_iter().map(|f| f.1).collect())
+        })
+        .collect()
+}
+
+/// Adds the animations whose name isn't taken yet, returns how many.
+pub fn add_missing(animations: &mut Vec<Animation>, found: Vec<Animation>) -> usize {
+    let before = animations.len();
+    for animation in found {
+        if !animations.iter().any(|a| a.name == animation.name) {
+            animations.push(animation);
+        }
+    }
+    animations.len() - before
+}
+
+/// Animations are kept next to the marked file in the same format,
+/// `level-marked.json` has `level-animations.json`.
+pub fn animations_path<P: AsRef<Path>>(marked_path: P) -> PathBuf {
+    let path = marked_path.as_ref();
+    let stem = path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let base = stem.trim_end_matches("-marked");
+    let ext = path.extension()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from(MarkedFormat::Json.extension()));
+
+    path.with_file_name(format!("{}-animations.{}", base, ext))
+}
+
+/// Loads animations, picking the format from the file extension.
+pub fn load_animations<P: AsRef<Path>>(path: P) -> GameResult<Vec<Animation>> {
+    let format = MarkedFormat::from_path(&path).unwrap_or(MarkedFormat::Json);
+    let file = File::open(path)?;
+
+    read_data(BufReader::new(file), format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprites(names: &[&str]) -> Vec<SpriteData> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/level_ground-marked.json");
+        let template = SpriteData::load_marked(path).unwrap().remove(0);
+        names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| SpriteData {
+                name: String::from(*name),
+                index,
+                ..template.clone()
+            })
+            .collect()
+    }
+
+    fn animation(durations: &[u32], loop_mode: LoopMode) -> Animation {
+        Animation {
+            name: String::from("test"),
+            frames: durations
+                .iter()
+                .enumerate()
+                .map(|(ix, &duration)| AnimationFrame {
+                    sprite: format!("frame {}", ix),
+                    duration,
+                })
+                .collect(),
+            loop_mode,
+        }
+    }
+
+    fn frames_at(animation: &Animation, times: &[u32]) -> Vec<Option<usize>> {
+        times.iter().map(|&t| animation.frame_at(t)).collect()
+    }
+
+    #[test]
+    fn sequence_names() {
+        assert_eq!(sequence_name("Bone (2).png"), Some((String::from("Bone"), 2)));
+        assert_eq!(sequence_name("Bone_02.png"), Some((String::from("Bone"), 2)));
+        assert_eq!(sequence_name("Tile (10).png"), Some((String::from("Tile"), 10)));
+        assert_eq!(sequence_name("run-3"), Some((String::from("run"), 3)));
+        assert_eq!(sequence_name("Bone.png"), None);
+        assert_eq!(sequence_name("Bone (a).png"), None);
+        assert_eq!(sequence_name("12.png"), None);
+    }
+
+    #[test]
+    fn auto_group_orders_frames_and_sequences() {
+        let marked = sprites(&[
+            "Run (2).png",
+            "Idle_1.png",
+            "Run (10).png",
+            "Bone.png",
+            "Run (1).png",
+            "Jump (1).png",
+            "Idle_2.png",
+        ]);
+        let animations = auto_group(&marked);
+
+        let names: Vec<&str> = animations.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["Run", "Idle"]);
+        let run: Vec<&str> = animations[0].frames.iter().map(|f| f.sprite.as_str()).collect();
+        assert_eq!(run, vec!["Run (1).png", "Run (2).png", "Run (10).png"]);
+        let idle: Vec<&str> = animations[1].frames.iter().map(|f| f.sprite.as_str()).collect();
+        assert_eq!(idle, vec!["Idle_1.png", "Idle_2.png"]);
+    }
+
+    #[test]
+    fn frame_at_loop() {
+        let a = animation(&[100, 200, 100], LoopMode::Loop);
+        assert_eq!(a.duration(), 400);
+        assert_eq!(
+            frames_at(&a, &[0, 99, 100, 299, 300, 399, 400, 500]),
+            vec![Some(0), Some(0), Some(1), Some(1), Some(2), Some(2), Some(0), Some(1)]
+        );
+    }
+
+    #[test]
+    fn frame_at_once() {
+        let a = animation(&[100, 200, 100], LoopMode::Once);
+        assert_eq!(
+            frames_at(&a, &[0, 100, 300, 399, 400, 10000]),
+            vec![Some(0), Some(1), Some(2), Some(2), Some(2), Some(2)]
+        );
+    }
+
+    #[test]
+    fn frame_at_ping_pong() {
+        // 0, 1, 2, 1 and again, the turning frames aren't shown twice.
+        let a = animation(&[100, 200, 100], LoopMode::PingPong);
+        assert_eq!(a.duration(), 600);
+        assert_eq!(
+            frames_at(&a, &[0, 100, 299, 300, 399, 400, 599, 600, 700]),
+            vec![
+                Some(0),
+                Some(1),
+                Some(1),
+                Some(2),
+                Some(2),
+                Some(1),
+                Some(1),
+                Some(0),
+                Some(1),
+            ]
+        );
+
+        let two = animation(&[100, 100], LoopMode::PingPong);
+        assert_eq!(frames_at(&two, &[0, 150, 200]), vec![Some(0), Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn frame_at_without_time() {
+        assert_eq!(animation(&[], LoopMode::Loop).frame_at(0), None);
+        assert_eq!(animation(&[0, 0], LoopMode::Loop).frame_at(50), Some(1));
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+use bincode;
+use ron;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use ggez::{GameError, GameResult};
+
+use super::SpriteData;
+
+/// On-disk representation of the marked data.
+///
+/// `Json` is what the editor has always written, `Ron` is meant for reviewing
+/// diffs by hand and `Binary` is a compact bincode encoding for loading at
+/// runtime. The format of a project is picked by the marked file extension.
+///
+/// Bincode isn't self-describing, binary files written before `SpriteData`
+/// gained a field have to be converted again from the JSON or RON source.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MarkedFormat {
+    Json,
+    Ron,
+    Binary,
+}
+
+impl MarkedFormat {
+    pub fn all() -> [MarkedFormat; 3] {
+        [MarkedFormat::Json, MarkedFormat::Ron, MarkedFormat::Binary]
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            MarkedFormat::Json => "json",
+            MarkedFormat::Ron => "ron",
+            MarkedFormat::Binary => "bin",
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<MarkedFormat> {
+        let ext = path.as_ref()
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase());
+
+        MarkedFormat::all()
+            .iter()
+            .find(|f| ext.as_ref().map(|e| e == f.extension()).unwrap_or(false))
+            .cloned()
+    }
+}
+
+pub fn read_marked<R: Read>(reader: R, format: MarkedFormat) -> GameResult<Vec<SpriteData>> {
+    read_data(reader, format)
+}
+
+pub fn write_marked<W: Write>(
+    writer: W,
+    format: MarkedFormat,
+    data: &[SpriteData],
+) -> GameResult<()> {
+    write_data(writer, format, &data)
+}
+
+/// Reads anything stored alongside the marked data, e.g. animations.
+pub fn read_data<T: DeserializeOwned, R: Read>(reader: R, format: MarkedFormat) -> GameResult<T> {
+    let result = match format {
+        MarkedFormat::Json => serde_json::from_reader(reader).map_err(|e| format!("{}", e)),
+        MarkedFormat::Ron => ron::de::from_reader(reader).map_err(|e| format!("{}", e)),
+        MarkedFormat::Binary => bincode::deserialize_from(reader).map_err(|e| format!("{}", e)),
+    };
+    result.map_err(GameError::ResourceLoadError)
+}
+
+pub fn write_data<T: Serialize, W: Write>(
+    mut writer: W,
+    format: MarkedFormat,
+    data: &T,
+) -> GameResult<()> {
+    let result = match format {
+        MarkedFormat::Json => {
+            serde_json::to_writer_pretty(writer, data).map_err(|e| format!("{}", e))
+        }
+        MarkedFormat::Ron => ron::ser::to_string_pretty(data, Default::default())
+            .map_err(|e| format!("{}", e))
+            .and_then(|s| {
+                writer
+                    .write_all(s.as_bytes())
+                    .map_err(|e| format!("{}", e))
+            }),
+        MarkedFormat::Binary => {
+            bincode::serialize_into(writer, data).map_err(|e| format!("{}", e))
+        }
+    };
+    result.map_err(GameError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    fn level_ground() -> Vec<SpriteData> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/level_ground-marked.json");
+        read_marked(File::open(path).unwrap(), MarkedFormat::Json).unwrap()
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        let data = level_ground();
+        assert!(!data.is_empty());
+
+        for &format in MarkedFormat::all().iter() {
+            let mut bytes = vec![];
+            write_marked(&mut bytes, format, &data).unwrap();
+            let read = read_marked(&bytes[..], format).unwrap();
+            assert_eq!(read, data, "{:?}", format);
+        }
+    }
+}
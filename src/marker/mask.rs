@@ -0,0 +1,294 @@
+//! Ground described by the neighbours that continue it, one bit per
+//! direction clockwise from north, instead of by 9-slice squares.
+
+use super::{Square, SpriteData, SpriteType};
+
+pub const N: u8 = 1;
+pub const NE: u8 = 2;
+pub const E: u8 = 4;
+pub const SE: u8 = 8;
+pub const S: u8 = 16;
+pub const SW: u8 = 32;
+pub const W: u8 = 64;
+pub const NW: u8 = 128;
+
+/// The four sides, what a 4-bit mask keeps.
+pub const SIDES: u8 = N | E | S | W;
+
+/// Neighbour bits in clockwise order starting from north, with their names.
+pub const NEIGHBOURS: [(u8, &'static str); 8] = [
+    (N, "N"),
+    (NE, "NE"),
+    (E, "E"),
+    (SE, "SE"),
+    (S, "S"),
+    (SW, "SW"),
+    (W, "W"),
+    (NW, "NW"),
+];
+
+/// Corner bits together with the two sides they sit between.
+pub const CORNERS: [(u8, u8, u8); 4] = [(NE, N, E), (SE, S, E), (SW, S, W), (NW, N, W)];
+
+/// Clears corners that can't be seen because an adjacent side is open.
+pub fn canonical(mask: u8) -> u8 {
+    CORNERS.iter().fold(mask, |m, &(corner, a, b)| {
+        if m & a == 0 || m & b == 0 {
+            m & !corner
+        } else {
+            m
+        }
+    })
+}
+
+pub fn mask_name(mask: u8) -> String {
+    let names: Vec<&str> = NEIGHBOURS
+        .iter()
+        .filter(|&&(bit, _)| mask & bit != 0)
+        .map(|&(_, name)| name)
+        .collect();
+    if names.is_empty() {
+        String::from("-")
+    } else {
+        names.join(" ")
+    }
+}
+
+/// Squares a ground tile has to be marked with to fill the configuration,
+/// `None` if the 9-slice squares can't express it (e.g. a one tile wide strip).
+pub fn required_squares(mask: u8) -> Option<Vec<Square>> {
+    let mask = canonical(mask);
+    let (n, e, s, w) = (mask & N != 0, mask & E != 0, mask & S != 0, mask & W != 0);
+
+    let mut squares = match (n, e, s, w) {
+        (true, true, true, true) => vec![],
+        (false, true, true, true) => vec![Square::MT],
+        (true, false, true, true) => vec![Square::RM],
+        (true, true, false, true) => vec![Square::MB],
+        (true, true, true, false) => vec![Square::LM],
+        (false, true, true, false) => vec![Square::LT],
+        (false, false, true, true) => vec![Square::RT],
+        (true, true, false, false) => vec![Square::LB],
+        (true, false, false, true) => vec![Square::RB],
+        _ => return None,
+    };
+
+    let inner = [
+        (NE, Square::IRT),
+        (SE, Square::IBR),
+        (SW, Square::IBL),
+        (NW, Square::ILT),
+    ];
+    for &(corner, ref sq) in inner.iter() {
+        let (_, a, b) = *CORNERS.iter().find(|c| c.0 == corner).unwrap();
+        if mask & a != 0 && mask & b != 0 && mask & corner == 0 {
+            squares.push(sq.clone());
+        }
+    }
+
+    if squares.is_empty() {
+        squares.push(Square::MM);
+    }
+    Some(squares)
+}
+
+impl Square {
+    /// Neighbours that don't continue the ground of a tile with this square.
+    pub fn open_bits(&self) -> u8 {
+        match *self {
+            Square::LT => W | NW | N | NE | SW,
+            Square::MT => NW | N | NE,
+            Square::RT => NW | N | NE | E | SE,
+            Square::LM => NW | W | SW,
+            Square::MM => 0,
+            Square::RM => NE | E | SE,
+            Square::LB => NW | W | SW | S | SE,
+            Square::MB => SW | S | SE,
+            Square::RB => NE | E | SE | S | SW,
+            Square::IBL => SW,
+            Square::ILT => NW,
+            Square::IBR => SE,
+            Square::IRT => NE,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum MaskMode {
+    /// Only the sides count, the 16 tiles of a 4-bit autotile set.
+    Sides,
+    /// Corners count between two filled sides, the 47 tiles of an 8-bit blob set.
+    Blob,
+}
+
+/// Neighbours a ground tile connects to. Can describe any tile, e.g. a
+/// top and right edge without the corner between them, which squares can't.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct GroundMask {
+    pub mode: MaskMode,
+    pub bits: u8,
+}
+
+impl GroundMask {
+    /// Drops the bits the mode doesn't use.
+    pub fn new(mode: MaskMode, bits: u8) -> GroundMask {
+        let bits = match mode {
+            MaskMode::Sides => bits & SIDES,
+            MaskMode::Blob => canonical(bits),
+        };
+        GroundMask { mode, bits }
+    }
+
+    /// Mask of a tile marked with `squares`, every square closing off the
+    /// neighbours it has an edge or an inner corner towards.
+    pub fn from_squares(squares: &[Square], mode: MaskMode) -> Option<GroundMask> {
+        if squares.is_empty() {
+            return None;
+        }
+        let open = squares.iter().fold(0, |open, s| open | s.open_bits());
+        Some(GroundMask::new(mode, !open))
+    }
+
+    pub fn has(&self, bit: u8) -> bool {
+        self.bits & bit != 0
+    }
+
+    pub fn toggled(&self, bit: u8) -> GroundMask {
+        GroundMask::new(self.mode, self.bits ^ bit)
+    }
+
+    /// Same tile as an 8-bit blob mask, a 4-bit tile has every corner
+    /// between its sides.
+    pub fn blob(&self) -> u8 {
+        match self.mode {
+            MaskMode::Sides => canonical(self.bits | !SIDES),
+            MaskMode::Blob => canonical(self.bits),
+        }
+    }
+
+    /// Lookup index, 0 to 15 with N = 1, E = 2, S = 4 and W = 8 for a 4-bit
+    /// mask, the blob bits otherwise.
+    pub fn index(&self) -> u8 {
+        match self.mode {
+            MaskMode::Sides => [N, E, S, W]
+                .iter()
+                .enumerate()
+                .filter(|&(_, &side)| self.has(side))
+                .fold(0, |index, (ix, _)| index | 1 << ix),
+            MaskMode::Blob => canonical(self.bits),
+        }
+    }
+
+    /// Squares describing the same tile, if they can.
+    pub fn squares(&self) -> Option<Vec<Square>> {
+        required_squares(self.blob())
+    }
+}
+
+/// Switches ground sprites to masks in `mode`, or masks back to squares for
+/// `None`. Returns the names of the sprites left as masks because squares
+/// can't express them.
+pub fn convert_ground(marked: &mut [SpriteData], mode: Option<MaskMode>) -> Vec<String> {
+    let mut kept = vec![];
+    for sd in marked.iter_mut() {
+        let converted = match (&sd.markers, mode) {
+            (&SpriteType::Ground { ref square }, Some(mode)) => {
+                GroundMask::from_squares(square, mode).map(|mask| SpriteType::GroundMask { mask })
+            }
+            (&SpriteType::GroundMask { mask }, Some(mode)) => Some(SpriteType::GroundMask {
+                mask: GroundMask::new(mode, mask.blob()),
+            }),
+            (&SpriteType::GroundMask { mask }, None) => match mask.squares() {
+                Some(square) => Some(SpriteType::Ground { square }),
+                None => {
+                    kept.push(sd.name.clone());
+                    None
+                }
+            },
+            _ => None,
+        };
+        if let Some(markers) = converted {
+            sd.markers = markers;
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::square_icons;
+    use coverage::blob_configurations;
+
+    #[test]
+    fn every_square_round_trips() {
+        assert_eq!(square_icons.len(), 13);
+        for icon in square_icons.iter() {
+            let square = vec![icon.s.clone()];
+            let ground = GroundMask::from_squares(&square, MaskMode::Blob).unwrap();
+            assert_eq!(ground.squares(), Some(square.clone()), "{:?}", icon.s);
+            assert_eq!(required_squares(ground.blob()), Some(square), "{:?}", icon.s);
+        }
+        assert_eq!(GroundMask::from_squares(&[], MaskMode::Blob), None);
+    }
+
+    #[test]
+    fn every_blob_configuration_round_trips() {
+        let all = blob_configurations();
+        assert_eq!(all.len(), 47);
+
+        for &bits in all.iter() {
+            let ground = GroundMask::new(MaskMode::Blob, bits);
+            assert_eq!(ground.blob(), bits);
+            assert_eq!(ground.index(), bits);
+            match required_squares(bits) {
+                Some(squares) => {
+                    assert_eq!(ground.squares(), Some(squares.clone()));
+                    let back = GroundMask::from_squares(&squares, MaskMode::Blob);
+                    assert_eq!(back, Some(ground), "{}", mask_name(bits));
+                }
+                None => assert_eq!(ground.squares(), None),
+            }
+        }
+    }
+
+    #[test]
+    fn sides_masks_round_trip() {
+        for bits in 0..16u8 {
+            let sides = [N, E, S, W]
+                .iter()
+                .enumerate()
+                .filter(|&(ix, _)| bits & 1 << ix != 0)
+                .fold(0, |sides, (_, &side)| sides | side);
+            let ground = GroundMask::new(MaskMode::Sides, sides | NE | SW);
+            assert_eq!(ground.bits, sides);
+            assert_eq!(ground.index(), bits);
+            assert_eq!(ground.blob(), canonical(sides | !SIDES));
+
+            if let Some(squares) = ground.squares() {
+                let back = GroundMask::from_squares(&squares, MaskMode::Sides);
+                assert_eq!(back, Some(ground), "{}", mask_name(sides));
+            }
+        }
+    }
+
+    #[test]
+    fn convert_ground_round_trips() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/level_ground-marked.json");
+        let original = SpriteData::load_marked(path).unwrap();
+        let mut marked = original.clone();
+
+        assert!(convert_ground(&mut marked, Some(MaskMode::Blob)).is_empty());
+        let masks = marked.iter().filter(|sd| sd.markers.ground_mask().is_some()).count();
+        assert_eq!(masks, 13);
+        assert!(convert_ground(&mut marked, None).is_empty());
+        assert_eq!(marked, original);
+
+        // A one tile wide strip can't go back to squares and stays a mask.
+        marked[4].markers = SpriteType::GroundMask {
+            mask: GroundMask::new(MaskMode::Blob, N | S),
+        };
+        assert_eq!(convert_ground(&mut marked, None), vec![marked[4].name.clone()]);
+        assert!(marked[4].markers.ground_mask().is_some());
+    }
+}
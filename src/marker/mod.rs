@@ -1,8 +1,25 @@
 
+mod animation;
+mod format;
+pub mod mask;
+mod sheet;
+mod store;
+
+pub use self::animation::*;
+pub use self::format::*;
+pub use self::mask::{GroundMask, MaskMode};
+pub use self::store::{backup_path, save_animations, save_file, save_marked, SaveOptions};
+pub use self::sheet::MarkedSheet;
+
 use super::sprite::geom;
 use super::sprite::*;
 use super::sprite::texture_packer::*;
 
+use std::path::Path;
+use std::fs::File;
+use std::io::BufReader;
+use ggez::GameResult;
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
 pub enum Square {
     LT,
@@ -119,14 +136,70 @@ pub enum Horizontal {
     Center,
 }
 
+/// Nine-slice borders in logical pixels from the frame edges. The corners
+/// keep their size, the edges and the center stretch.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub struct Insets {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+impl Insets {
+    /// Whether the borders leave room for the stretched middle of a frame.
+    pub fn fits(&self, frame: &geom::Rect) -> bool {
+        (self.left + self.right) as f32 <= frame.w && (self.top + self.bottom) as f32 <= frame.h
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
 pub enum SpriteType {
     Object,
     Platform { horizontal: Vec<Horizontal> },
     Ground { square: Vec<Square> },
+    NineSlice { insets: Insets },
+    /// Ground given by its neighbours rather than squares, same kind as `Ground`.
+    GroundMask { mask: GroundMask },
+}
+
+/// Marker category without the payload, handy as a map key.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum SpriteKind {
+    Object,
+    Platform,
+    Ground,
+    NineSlice,
 }
 
 impl SpriteType {
+    pub fn kind(&self) -> SpriteKind {
+        match *self {
+            SpriteType::Object => SpriteKind::Object,
+            SpriteType::Platform { .. } => SpriteKind::Platform,
+            SpriteType::Ground { .. } => SpriteKind::Ground,
+            SpriteType::NineSlice { .. } => SpriteKind::NineSlice,
+            SpriteType::GroundMask { .. } => SpriteKind::Ground,
+        }
+    }
+
+    pub fn insets(&self) -> Option<Insets> {
+        match *self {
+            SpriteType::NineSlice { insets } => Some(insets),
+            _ => None,
+        }
+    }
+
+    /// Neighbour mask of either ground representation, squares are
+    /// converted to an 8-bit mask.
+    pub fn ground_mask(&self) -> Option<GroundMask> {
+        match *self {
+            SpriteType::Ground { ref square } => GroundMask::from_squares(square, MaskMode::Blob),
+            SpriteType::GroundMask { mask } => Some(mask),
+            _ => None,
+        }
+    }
+
     pub fn empty_ground() -> SpriteType {
         SpriteType::Ground { square: vec![] }
     }
@@ -140,23 +213,44 @@ impl SpriteType {
 
 // impl Deserialize for SpriteType {}
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SpriteData {
     pub on_screen_frame: geom::Rect,
+    /// Frame in logical units, the atlas pixels of the @1x sheet.
     pub frame: geom::Rect,
     pub markers: SpriteType,
     pub name: String,
     pub index: usize,
+    /// Page of a multi-pack sheet the frame is on.
+    #[serde(default)]
+    pub page: usize,
+    /// Origin of the sprite relative to its untrimmed source size, like
+    /// TexturePacker's `pivot`.
+    #[serde(default = "default_pivot")]
+    pub pivot: geom::Point,
+    /// Free-form labels like a biome or level, kept normalized and sorted.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_pivot() -> geom::Point {
+    geom::Point { x: 0.5, y: 0.5 }
 }
 
 impl SpriteData {
     pub fn create(info: &SpriteSheetInfo) -> Vec<SpriteData> {
-        let frames = FrameInfo::extract_frames(&info);
-        let marked: Vec<SpriteData> = info.frames
+        SpriteData::create_pages(&[info.clone()])
+    }
+
+    /// Unmarked data for every frame of a multi-pack sheet, indexed across pages.
+    pub fn create_pages(pages: &[SpriteSheetInfo]) -> Vec<SpriteData> {
+        let frames = FrameInfo::extract_pages(pages);
+        let marked: Vec<SpriteData> = pages
             .iter()
+            .flat_map(|info| info.frames.iter().map(move |sd| (info, sd)))
             .enumerate()
-            .map(|(ix, sd)| {
-                let on_image_frame = sd.frame.clone();
+            .map(|(ix, (info, sd))| {
+                let on_image_frame = info.logical_frame(sd);
                 let on_screen_frame = frames[ix].segment;
                 SpriteData {
                     on_screen_frame: geom::Rect::from(on_screen_frame),
@@ -164,9 +258,75 @@ impl SpriteData {
                     markers: SpriteType::Ground { square: vec![] },
                     name: sd.filename.clone(),
                     index: ix,
+                    page: frames[ix].page,
+                    pivot: sd.pivot.clone(),
+                    tags: vec![],
                 }
             })
             .collect();
         marked
     }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| *t == normalize_tag(tag))
+    }
+
+    /// Returns whether the tag was new.
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        if tag.is_empty() || self.tags.contains(&tag) {
+            return false;
+        }
+        self.tags.push(tag);
+        self.tags.sort();
+        true
+    }
+
+    /// Returns whether the sprite had the tag.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        let before = self.tags.len();
+        self.tags.retain(|t| *t != tag);
+        self.tags.len() != before
+    }
+
+    /// Whether the sprite passes a grid filter. Every word has to match,
+    /// `#cave` matches the tag and anything else a part of the name.
+    pub fn matches(&self, filter: &str) -> bool {
+        let name = self.name.to_lowercase();
+        filter.split_whitespace().all(|word| {
+            if word.starts_with('#') {
+                // A lone `#` is a tag still being typed.
+                word.len() == 1 || self.has_tag(word)
+            } else {
+                name.contains(&word.to_lowercase())
+            }
+        })
+    }
+
+    /// Loads marked data, picking the format from the file extension.
+    pub fn load_marked<P: AsRef<Path>>(path: P) -> GameResult<Vec<SpriteData>> {
+        let format = MarkedFormat::from_path(&path).unwrap_or(MarkedFormat::Json);
+        let file = File::open(path)?;
+
+        read_marked(BufReader::new(file), format)
+    }
+}
+
+/// Tags are lowercase without spaces, so `Ice Cave` and `#ice-cave` are
+/// the same tag.
+pub fn normalize_tag(tag: &str) -> String {
+    let words: Vec<String> = tag.trim_start_matches('#')
+        .split_whitespace()
+        .map(|w| w.to_lowercase())
+        .collect();
+    words.join("-")
+}
+
+/// Every tag used in the sheet, sorted.
+pub fn all_tags(marked: &[SpriteData]) -> Vec<String> {
+    let mut tags: Vec<String> = marked.iter().flat_map(|sd| sd.tags.iter().cloned()).collect();
+    tags.sort();
+    tags.dedup();
+    tags
 }
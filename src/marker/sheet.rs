@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use ggez::GameResult;
+
+use super::*;
+use super::super::sprite::geom;
+
+/// Marked sprites indexed for the lookups a game does at runtime,
+/// e.g. "the ground tile with exactly a top edge".
+#[derive(Debug, Clone)]
+pub struct MarkedSheet {
+    sprites: Vec<SpriteData>,
+    /// Indices of every sprite with the name, names repeat across the pages
+    /// of a multi-pack sheet.
+    by_name: HashMap<String, Vec<usize>>,
+    by_kind: HashMap<SpriteKind, Vec<usize>>,
+    animations: Vec<Animation>,
+}
+
+impl MarkedSheet {
+    pub fn new(sprites: Vec<SpriteData>) -> MarkedSheet {
+        let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut by_kind: HashMap<SpriteKind, Vec<usize>> = HashMap::new();
+
+        for (ix, sd) in sprites.iter().enumerate() {
+            by_name.entry(sd.name.clone()).or_insert_with(Vec::new).push(ix);
+            by_kind.entry(sd.markers.kind()).or_insert_with(Vec::new).push(ix);
+        }
+
+        MarkedSheet {
+            sprites,
+            by_name,
+            by_kind,
+            animations: vec![],
+        }
+    }
+
+    pub fn with_animations(mut self, animations: Vec<Animation>) -> MarkedSheet {
+        self.animations = animations;
+        self
+    }
+
+    /// Loads the marked file and the animations next to it, if there are any.
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<MarkedSheet> {
+        let sheet = SpriteData::load_marked(&path).map(MarkedSheet::new)?;
+        let animations = animations_path(&path);
+        if animations.exists() {
+            Ok(sheet.with_animations(load_animations(animations)?))
+        } else {
+            Ok(sheet)
+        }
+    }
+
+    pub fn sprites(&self) -> &[SpriteData] {
+        &self.sprites
+    }
+
+    /// First sprite with the name, see `all_named` for repeated names.
+    pub fn by_name(&self, name: &str) -> Option<&SpriteData> {
+        self.by_name.get(name).map(|ixs| &self.sprites[ixs[0]])
+    }
+
+    /// Every sprite with the name, in sheet order.
+    pub fn all_named(&self, name: &str) -> Vec<&SpriteData> {
+        self.by_name
+            .get(name)
+            .map(|ixs| ixs.iter().map(|ix| &self.sprites[*ix]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Names shared by more than one sprite, sorted.
+    pub fn duplicate_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.by_name
+            .iter()
+            .filter(|&(_, ixs)| ixs.len() > 1)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn by_kind(&self, kind: SpriteKind) -> Vec<&SpriteData> {
+        self.by_kind
+            .get(&kind)
+            .map(|ixs| ixs.iter().map(|ix| &self.sprites[*ix]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Ground sprites marked with exactly `squares`, order doesn't matter.
+    pub fn ground_exact(&self, squares: &[Square]) -> Vec<&SpriteData> {
+        let wanted: HashSet<&Square> = squares.iter().collect();
+        self.ground_matching(|have| *have == wanted)
+    }
+
+    /// Ground sprites marked with at least `squares`.
+    pub fn ground_superset(&self, squares: &[Square]) -> Vec<&SpriteData> {
+        let wanted: HashSet<&Square> = squares.iter().collect();
+        self.ground_matching(|have| have.is_superset(&wanted))
+    }
+
+    fn ground_matching<F>(&self, pred: F) -> Vec<&SpriteData>
+    where
+        F: Fn(&HashSet<&Square>) -> bool,
+    {
+        self.by_kind(SpriteKind::Ground)
+            .into_iter()
+            .filter(|sd| match sd.markers {
+                SpriteType::Ground { ref square } => pred(&square.iter().collect()),
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// Ground sprites filling the neighbour configuration, whether they are
+    /// marked with squares or a mask. `bits` is an 8-bit blob mask.
+    pub fn ground_mask(&self, bits: u8) -> Vec<&SpriteData> {
+        let wanted = mask::canonical(bits);
+        self.by_kind(SpriteKind::Ground)
+            .into_iter()
+            .filter(|sd| sd.markers.ground_mask().map(|m| m.blob()) == Some(wanted))
+            .collect()
+    }
+
+    pub fn platform_with(&self, horizontal: &Horizontal) -> Vec<&SpriteData> {
+        self.by_kind(SpriteKind::Platform)
+            .into_iter()
+            .filter(|sd| match sd.markers {
+                SpriteType::Platform { horizontal: ref h } => h.contains(horizontal),
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// Sprites with the tag, in sheet order.
+    pub fn by_tag(&self, tag: &str) -> Vec<&SpriteData> {
+        self.sprites.iter().filter(|sd| sd.has_tag(tag)).collect()
+    }
+
+    pub fn tags(&self) -> Vec<String> {
+        all_tags(&self.sprites)
+    }
+
+    pub fn animations(&self) -> &[Animation] {
+        &self.animations
+    }
+
+    pub fn animation(&self, name: &str) -> Option<&Animation> {
+        self.animations.iter().find(|a| a.name == name)
+    }
+
+    /// Nine-slice borders of a UI sprite.
+    pub fn insets(&self, name: &str) -> Option<Insets> {
+        self.by_name(name).and_then(|sd| sd.markers.insets())
+    }
+
+    /// Frame of the sprite in logical units, the atlas pixels of the @1x sheet.
+    pub fn frame(&self, name: &str) -> Option<&geom::Rect> {
+        self.by_name(name).map(|sd| &sd.frame)
+    }
+
+    /// Frame of the sprite in normalized texture coordinates.
+    pub fn uv(&self, name: &str) -> Option<&geom::Rect> {
+        self.by_name(name).map(|sd| &sd.on_screen_frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level_ground() -> MarkedSheet {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/level_ground-marked.json");
+        MarkedSheet::load(path).unwrap()
+    }
+
+    fn names(sprites: Vec<&SpriteData>) -> Vec<&str> {
+        sprites.iter().map(|sd| sd.name.as_str()).collect()
+    }
+
+    #[test]
+    fn by_name() {
+        let sheet = level_ground();
+        assert_eq!(sheet.by_name("Tile (5).png").unwrap().index, 8);
+        assert!(sheet.by_name("Tile (99).png").is_none());
+        assert!(sheet.duplicate_names().is_empty());
+    }
+
+    #[test]
+    fn repeated_names_are_kept() {
+        let mut sprites = level_ground().sprites().to_vec();
+        let mut copy = sprites[0].clone();
+        copy.page = 1;
+        sprites.push(copy);
+        let sheet = MarkedSheet::new(sprites);
+
+        assert_eq!(sheet.by_name("Bone (1).png").unwrap().page, 0);
+        let pages: Vec<usize> = sheet.all_named("Bone (1).png").iter().map(|sd| sd.page).collect();
+        assert_eq!(pages, vec![0, 1]);
+        assert_eq!(sheet.duplicate_names(), vec!["Bone (1).png"]);
+    }
+
+    #[test]
+    fn by_kind() {
+        let sheet = level_ground();
+        assert_eq!(sheet.by_kind(SpriteKind::Object).len(), 4);
+        assert_eq!(sheet.by_kind(SpriteKind::Ground).len(), 13);
+        assert_eq!(
+            names(sheet.by_kind(SpriteKind::Platform)),
+            vec!["Tile (14).png", "Tile (15).png", "Tile (16).png"]
+        );
+    }
+
+    #[test]
+    fn ground_exact() {
+        let sheet = level_ground();
+        assert_eq!(names(sheet.ground_exact(&[Square::MT])), vec!["Tile (2).png"]);
+        assert!(sheet.ground_exact(&[Square::MT, Square::LT]).is_empty());
+        assert!(sheet.ground_exact(&[]).is_empty());
+    }
+
+    #[test]
+    fn ground_superset() {
+        let sheet = level_ground();
+        assert_eq!(names(sheet.ground_superset(&[Square::IBL])), vec!["Tile (10).png"]);
+        assert_eq!(sheet.ground_superset(&[]).len(), 13);
+        assert!(sheet.ground_superset(&[Square::LT, Square::RT]).is_empty());
+    }
+
+    #[test]
+    fn platform_with() {
+        let sheet = level_ground();
+        assert_eq!(names(sheet.platform_with(&Horizontal::Left)), vec!["Tile (14).png"]);
+        assert_eq!(names(sheet.platform_with(&Horizontal::Right)), vec!["Tile (16).png"]);
+    }
+}
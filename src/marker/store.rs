@@ -0,0 +1,107 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use ggez::{GameError, GameResult};
+use serde::Serialize;
+
+use super::*;
+
+#[derive(Debug, Clone)]
+pub struct SaveOptions {
+    /// Format to write, picked from the extension when `None`.
+    pub format: Option<MarkedFormat>,
+    /// How many previous versions to keep as `<file>.1` .. `<file>.N`.
+    pub backups: usize,
+}
+
+impl Default for SaveOptions {
+    fn default() -> SaveOptions {
+        SaveOptions {
+            format: None,
+            backups: 3,
+        }
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name: OsString = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+pub fn backup_path<P: AsRef<Path>>(path: P, n: usize) -> PathBuf {
+    with_suffix(path.as_ref(), &format!(".{}", n))
+}
+
+fn rotate_backups(path: &Path, backups: usize) -> GameResult<()> {
+    if backups == 0 || !path.exists() {
+        return Ok(());
+    }
+
+    for n in (1..backups).rev() {
+        let older = backup_path(path, n);
+        if older.exists() {
+            fs::rename(&older, backup_path(path, n + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Saves marked data without ever leaving a truncated file behind: the data
+/// goes to a temporary file first, which replaces the old one only once it
+/// is fully written and synced. The replaced version is kept as a backup.
+pub fn save_marked<P: AsRef<Path>>(
+    path: P,
+    data: &[SpriteData],
+    options: &SaveOptions,
+) -> GameResult<()> {
+    save(path.as_ref(), &data, options)
+}
+
+/// Saves the animations of a sheet, as safely as `save_marked`.
+pub fn save_animations<P: AsRef<Path>>(
+    path: P,
+    animations: &[Animation],
+    options: &SaveOptions,
+) -> GameResult<()> {
+    save(path.as_ref(), &animations, options)
+}
+
+/// Replaces any other file as safely as `save_marked`, e.g. a sheet meta.
+pub fn save_file<P: AsRef<Path>>(path: P, contents: &[u8], backups: usize) -> GameResult<()> {
+    replace(path.as_ref(), backups, |writer| Ok(writer.write_all(contents)?))
+}
+
+fn save<T: Serialize>(path: &Path, data: &T, options: &SaveOptions) -> GameResult<()> {
+    let format = options
+        .format
+        .or_else(|| MarkedFormat::from_path(path))
+        .unwrap_or(MarkedFormat::Json);
+    replace(path, options.backups, |writer| write_data(writer, format, data))
+}
+
+fn replace<F>(path: &Path, backups: usize, write: F) -> GameResult<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> GameResult<()>,
+{
+    let tmp = with_suffix(path, ".tmp");
+
+    let written = File::create(&tmp).map_err(GameError::from).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        Ok(())
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+
+    rotate_backups(path, backups)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use ggez::{GameError, GameResult};
+use serde_json;
+
+use marker;
+
+const APP_DIR: &'static str = "tilesheet-marker";
+const MAX_RECENT: usize = 10;
+
+/// Meta and atlas pair of a sheet opened before.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Recent {
+    pub meta: PathBuf,
+    pub image: PathBuf,
+}
+
+/// Where the editor was when a sheet was closed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Session {
+    pub scroll: f32,
+    /// Name of the selected sprite, survives reordering of the sheet.
+    pub selected: Option<String>,
+    pub filter: String,
+}
+
+/// Editor state kept between launches in the user's config directory.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct State {
+    /// Most recent first.
+    pub recent: Vec<Recent>,
+    /// Keyed by the absolute meta path, kept for the recent sheets only.
+    pub sessions: HashMap<String, Session>,
+}
+
+/// `$XDG_CONFIG_HOME`, `~/.config` or `%APPDATA%`, with the app's own directory.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    base.map(|dir| dir.join(APP_DIR))
+}
+
+fn state_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("state.json"))
+}
+
+fn key(meta: &Path) -> String {
+    let absolute = fs::canonicalize(meta).unwrap_or_else(|_| meta.to_path_buf());
+    format!("{}", absolute.display())
+}
+
+impl State {
+    /// Missing or unreadable state starts fresh rather than failing the launch.
+    pub fn load() -> State {
+        state_path()
+            .and_then(|path| File::open(path).ok())
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .map(State::pruned)
+            .unwrap_or_default()
+    }
+
+    /// Drops sheets that were deleted or moved since.
+    fn pruned(mut self) -> State {
+        self.recent.retain(|r| r.meta.exists());
+        self.sessions.retain(|meta, _| Path::new(meta).exists());
+        self
+    }
+
+    pub fn save(&self) -> GameResult<()> {
+        let path = state_path()
+            .ok_or_else(|| GameError::from(String::from("no config directory")))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_vec_pretty(self).map_err(|e| GameError::from(format!("{}", e)))?;
+        marker::save_file(path, &json, 0)
+    }
+
+    /// Moves the sheet to the front of the recent list, sheets falling off the
+    /// end lose their session.
+    pub fn opened(&mut self, meta: &Path, image: &Path) {
+        let recent = Recent {
+            meta: fs::canonicalize(meta).unwrap_or_else(|_| meta.to_path_buf()),
+            image: fs::canonicalize(image).unwrap_or_else(|_| image.to_path_buf()),
+        };
+        self.recent.retain(|r| r.meta != recent.meta);
+        self.recent.insert(0, recent);
+        self.recent.truncate(MAX_RECENT);
+
+        let kept: Vec<String> = self.recent.iter().map(|r| key(&r.meta)).collect();
+        self.sessions.retain(|meta, _| kept.contains(meta));
+    }
+
+    /// Recent sheets that still exist.
+    pub fn recent(&self) -> Vec<Recent> {
+        self.recent
+            .iter()
+            .filter(|r| r.meta.exists() && r.image.exists())
+            .cloned()
+            .collect()
+    }
+
+    pub fn session(&self, meta: &Path) -> Option<&Session> {
+        self.sessions.get(&key(meta))
+    }
+
+    pub fn set_session(&mut self, meta: &Path, session: Session) {
+        self.sessions.insert(key(meta), session);
+    }
+}
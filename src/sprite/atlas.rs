@@ -0,0 +1,264 @@
+use std::fs::File;
+use std::io::Read;
+use std::mem;
+use std::path::Path;
+
+use ggez::{Context, GameError, GameResult};
+use image;
+use image::{ImageFormat, Rgba, RgbaImage};
+
+use super::texture_packer::SpriteSheetMeta;
+
+/// Largest atlas side ggez can upload, `Image::from_rgba8` takes `u16`s.
+const UPLOAD_LIMIT: u32 = ::std::u16::MAX as u32;
+
+/// File extensions of the supported atlas formats.
+pub const EXTENSIONS: &'static [&'static str] = &["png", "webp", "tga", "dds", "bmp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtlasFormat {
+    Png,
+    WebP,
+    Tga,
+    Dds,
+    Bmp,
+}
+
+impl AtlasFormat {
+    /// Sniffs the file header. TGA has no magic number, so it's only picked
+    /// by the extension.
+    pub fn detect(bytes: &[u8], path: &Path) -> Option<AtlasFormat> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(AtlasFormat::Png)
+        } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(AtlasFormat::WebP)
+        } else if bytes.starts_with(b"DDS ") {
+            Some(AtlasFormat::Dds)
+        } else if bytes.starts_with(b"BM") {
+            Some(AtlasFormat::Bmp)
+        } else {
+            let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+            match ext.as_ref().map(|e| e.as_str()) {
+                Some("tga") => Some(AtlasFormat::Tga),
+                _ => None,
+            }
+        }
+    }
+}
+
+fn load_error<E: ::std::fmt::Display>(e: E) -> GameError {
+    GameError::ResourceLoadError(format!("{}", e))
+}
+
+/// Reads an atlas in any of the supported formats.
+pub fn read<P: AsRef<Path>>(path: P) -> GameResult<RgbaImage> {
+    let mut bytes = vec![];
+    File::open(&path)?.read_to_end(&mut bytes)?;
+
+    let format = AtlasFormat::detect(&bytes, path.as_ref()).ok_or_else(|| {
+        GameError::ResourceLoadError(format!(
+            "{} is not a PNG, WebP, TGA, DDS or BMP image",
+            path.as_ref().display()
+        ))
+    })?;
+
+    let img = match format {
+        AtlasFormat::Dds => decode_dds(&bytes)?,
+        AtlasFormat::Png => decode(&bytes, ImageFormat::PNG)?,
+        AtlasFormat::WebP => decode(&bytes, ImageFormat::WEBP)?,
+        AtlasFormat::Tga => decode(&bytes, ImageFormat::TGA)?,
+        AtlasFormat::Bmp => decode(&bytes, ImageFormat::BMP)?,
+    };
+    Ok(img)
+}
+
+fn decode(bytes: &[u8], format: ImageFormat) -> GameResult<RgbaImage> {
+    image::load_from_memory_with_format(bytes, format)
+        .map(|img| img.to_rgba())
+        .map_err(load_error)
+}
+
+type GLenum = u32;
+type GLint = i32;
+type GetIntegerv = unsafe extern "system" fn(GLenum, *mut GLint);
+
+/// `GL_MAX_TEXTURE_SIZE` of the context's GPU, `None` if it can't be asked.
+fn gl_max_texture_size(ctx: &Context) -> Option<u32> {
+    const GL_MAX_TEXTURE_SIZE: GLenum = 0x0D33;
+    let video = ctx.sdl_context.video().ok()?;
+    let address = video.gl_get_proc_address("glGetIntegerv");
+    if address.is_null() {
+        return None;
+    }
+    let mut size: GLint = 0;
+    // Sheets are loaded on the thread running the event loop, which is
+    // where ggez made its GL context current.
+    unsafe {
+        let get_integer: GetIntegerv = mem::transmute(address);
+        get_integer(GL_MAX_TEXTURE_SIZE, &mut size);
+    }
+    if size > 0 {
+        Some(size as u32)
+    } else {
+        None
+    }
+}
+
+/// Largest atlas side the window can show, the GPU's limit capped by what
+/// ggez uploads. Headless tools never upload and don't need to check.
+pub fn max_texture_size(ctx: &Context) -> u32 {
+    gl_max_texture_size(ctx).map_or(UPLOAD_LIMIT, |size| size.min(UPLOAD_LIMIT))
+}
+
+pub fn check_dimensions<P: AsRef<Path>>(path: P, img: &RgbaImage, max: u32) -> GameResult<()> {
+    let (width, height) = img.dimensions();
+    if width > max || height > max {
+        Err(GameError::ResourceLoadError(format!(
+            "{} is {}x{}, this GPU shows atlases of at most {}x{}",
+            path.as_ref().display(),
+            width,
+            height,
+            max,
+            max
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    bytes[offset] as u32
+        | (bytes[offset + 1] as u32) << 8
+        | (bytes[offset + 2] as u32) << 16
+        | (bytes[offset + 3] as u32) << 24
+}
+
+/// Scales the masked bits of `pixel` to 0..255, missing channels read as `default`.
+fn channel(pixel: u32, mask: u32, default: u8) -> u8 {
+    if mask == 0 {
+        return default;
+    }
+    let shift = mask.trailing_zeros();
+    let max = (mask >> shift) as u64;
+    (((pixel & mask) >> shift) as u64 * 255 / max) as u8
+}
+
+/// Uncompressed DDS with bit masks, as TexturePacker writes for RGBA formats.
+/// Block compressed textures are left to the game's own tools.
+fn decode_dds(bytes: &[u8]) -> GameResult<RgbaImage> {
+    const HEADER: usize = 128;
+    const DDPF_ALPHAPIXELS: u32 = 0x1;
+    const DDPF_FOURCC: u32 = 0x4;
+
+    if bytes.len() < HEADER {
+        return Err(load_error("truncated DDS header"));
+    }
+    let height = u32_at(bytes, 12);
+    let width = u32_at(bytes, 16);
+    let flags = u32_at(bytes, 80);
+    let bit_count = u32_at(bytes, 88);
+    let masks = [
+        u32_at(bytes, 92),
+        u32_at(bytes, 96),
+        u32_at(bytes, 100),
+        if flags & DDPF_ALPHAPIXELS != 0 {
+            u32_at(bytes, 104)
+        } else {
+            0
+        },
+    ];
+
+    if flags & DDPF_FOURCC != 0 {
+        return Err(load_error("compressed DDS atlases are not supported"));
+    }
+    if bit_count % 8 != 0 || bit_count == 0 || bit_count > 32 {
+        return Err(load_error(format!(
+            "unsupported DDS pixel size of {} bits",
+            bit_count
+        )));
+    }
+
+    let bpp = (bit_count / 8) as usize;
+    let pixels = &bytes[HEADER..];
+    if pixels.len() < width as usize * height as usize * bpp {
+        return Err(load_error("truncated DDS pixel data"));
+    }
+
+    Ok(RgbaImage::from_fn(width, height, |x, y| {
+        let offset = (y as usize * width as usize + x as usize) * bpp;
+        let pixel = (0..bpp).fold(0u32, |p, b| p | (pixels[offset + b] as u32) << (8 * b));
+        Rgba([
+            channel(pixel, masks[0], 0),
+            channel(pixel, masks[1], 0),
+            channel(pixel, masks[2], 0),
+            channel(pixel, masks[3], 255),
+        ])
+    }))
+}
+
+/// Bits per channel of TexturePacker's `meta.format`, red, green, blue, alpha.
+/// Zero alpha bits means the format is opaque.
+fn channel_bits(format: &str) -> Option<[u8; 4]> {
+    match format {
+        "RGBA8888" | "BGRA8888" | "ARGB8888" => Some([8, 8, 8, 8]),
+        "RGBA4444" => Some([4, 4, 4, 4]),
+        "RGBA5551" => Some([5, 5, 5, 1]),
+        "RGBA5555" => Some([5, 5, 5, 5]),
+        "RGB888" => Some([8, 8, 8, 0]),
+        "RGB565" => Some([5, 6, 5, 0]),
+        _ => None,
+    }
+}
+
+fn quantize(value: u8, bits: u8) -> u8 {
+    if bits >= 8 {
+        return value;
+    }
+    let levels = (1u32 << bits) - 1;
+    let q = (value as u32 * levels + 127) / 255;
+    (q * 255 / levels) as u8
+}
+
+/// Makes the preview look like the game: reduces the color depth to the
+/// sheet's pixel format and undoes premultiplied alpha, ggez blends
+/// straight alpha.
+pub fn apply_meta_format(img: &mut RgbaImage, meta: &SpriteSheetMeta) {
+    let bits = channel_bits(&meta.format);
+    if bits.is_none() && !meta.premultiplied_alpha {
+        return;
+    }
+
+    for pixel in img.pixels_mut() {
+        if let Some(bits) = bits {
+            for c in 0..3 {
+                pixel.0[c] = quantize(pixel.0[c], bits[c]);
+            }
+            pixel.0[3] = match bits[3] {
+                0 => 255,
+                b => quantize(pixel.0[3], b),
+            };
+        }
+        if meta.premultiplied_alpha && pixel.0[3] > 0 {
+            let a = pixel.0[3] as u32;
+            for c in 0..3 {
+                pixel.0[c] = (pixel.0[c] as u32 * 255 / a).min(255) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_expand_to_full_range() {
+        // RGB565
+        assert_eq!(channel(0xf800, 0xf800, 0), 255);
+        assert_eq!(channel(0x07e0, 0x07e0, 0), 255);
+        assert_eq!(channel(0x0400, 0x07e0, 0), 129);
+        assert_eq!(channel(0, 0x001f, 0), 0);
+        assert_eq!(channel(0xffff_ffff, 0xffff_ffff, 0), 255);
+        assert_eq!(channel(0x1234, 0, 200), 200);
+    }
+}
@@ -12,6 +12,15 @@ impl Rect {
     pub fn new(x: f32, y: f32, w: f32, h: f32) -> Rect {
         Rect { x, y, w, h }
     }
+
+    pub fn scaled(&self, factor: f32) -> Rect {
+        Rect::new(
+            self.x * factor,
+            self.y * factor,
+            self.w * factor,
+            self.h * factor,
+        )
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
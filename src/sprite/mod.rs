@@ -1,9 +1,10 @@
 pub mod texture_packer;
 pub mod geom;
+pub mod atlas;
+pub mod variants;
 
-use std::path::Path;
-use std::fs::File;
-use std::io::BufReader;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use super::sprite::texture_packer::*;
@@ -12,10 +13,9 @@ use super::sprite::geom::*;
 use ggez;
 use ggez::Context;
 use ggez::graphics::Image;
-use ggez::GameResult;
+use ggez::{GameError, GameResult};
 
-use image;
-use image::ImageFormat;
+use image::RgbaImage;
 
 pub struct Loader;
 
@@ -24,47 +24,180 @@ impl Loader {
         SpriteSheetInfo::load_info(path)
     }
 
-    fn load_image<P: AsRef<Path>>(ctx: &mut Context, path: P) -> GameResult<Image> {
-        let img = {
-            let file = File::open(path).unwrap();
-            let reader = BufReader::new(file);
-            image::load(reader, ImageFormat::PNG).unwrap().to_rgba()
-        };
+    /// Decodes the atlas without needing a ggez context, for headless tools.
+    /// The format is detected from the file contents.
+    pub fn load_rgba<P: AsRef<Path>>(path: P) -> GameResult<RgbaImage> {
+        atlas::read(path)
+    }
+
+    /// Atlas named by the meta's `image`, relative to the meta file.
+    pub fn resolve_image<P: AsRef<Path>>(meta: P, info: &SpriteSheetInfo) -> GameResult<PathBuf> {
+        let path = info.image_path(&meta);
+        if path.exists() {
+            Ok(path)
+        } else {
+            Err(GameError::ResourceLoadError(format!(
+                "{} names the atlas {}, which doesn't exist",
+                meta.as_ref().display(),
+                path.display()
+            )))
+        }
+    }
+
+    /// Frames are cut out relative to `meta.size`, an atlas of another size
+    /// means the meta and image are from different exports.
+    pub fn check_size<P: AsRef<Path>>(
+        info: &SpriteSheetInfo,
+        image: P,
+        img: &RgbaImage,
+    ) -> GameResult<()> {
+        let (width, height) = img.dimensions();
+        let Size { w, h } = info.meta.size;
+        if width as f32 == w && height as f32 == h {
+            Ok(())
+        } else {
+            Err(GameError::ResourceLoadError(format!(
+                "{} is {}x{} but the sheet's meta.size is {}x{}, re-export the sheet or pick the matching image",
+                image.as_ref().display(),
+                width,
+                height,
+                w,
+                h
+            )))
+        }
+    }
+
+    fn load_image<P: AsRef<Path>>(
+        ctx: &mut Context,
+        info: &SpriteSheetInfo,
+        path: P,
+    ) -> GameResult<Image> {
+        let mut img = Loader::load_rgba(&path)?;
+        atlas::check_dimensions(&path, &img, atlas::max_texture_size(ctx))?;
+        Loader::check_size(info, &path, &img)?;
+        atlas::apply_meta_format(&mut img, &info.meta);
         let (width, height) = img.dimensions();
         Image::from_rgba8(ctx, width as u16, height as u16, &img)
     }
 
+    /// Meta files of every page of a multi-pack sheet, following
+    /// `related_multi_packs` from `meta`. Sorted by file name so the page
+    /// numbers don't depend on which page was opened.
+    pub fn page_paths<P: AsRef<Path>>(meta: P) -> GameResult<Vec<PathBuf>> {
+        let mut pages = vec![meta.as_ref().to_path_buf()];
+        let mut next = 0;
+        while next < pages.len() {
+            let info = Loader::load_meta(&pages[next])?;
+            let dir = pages[next].parent().map(|d| d.to_path_buf()).unwrap_or_default();
+            for related in info.meta.related_multi_packs.iter() {
+                let path = dir.join(related);
+                if !pages.iter().any(|p| same_file(p, &path)) {
+                    pages.push(path);
+                }
+            }
+            next += 1;
+        }
+        pages.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+        Ok(pages)
+    }
+
+    /// Metas of every page, without images, for headless tools.
+    pub fn load_pages<P: AsRef<Path>>(meta: P) -> GameResult<Vec<SpriteSheetInfo>> {
+        Loader::page_paths(meta)?
+            .iter()
+            .map(|p| Loader::load_meta(p))
+            .collect()
+    }
+
+    /// Atlas of every page, for headless tools. `image` is the atlas of
+    /// `meta` and the other pages use the atlas their meta names.
+    pub fn load_page_images<P: AsRef<Path>>(meta: P, image: P) -> GameResult<Vec<RgbaImage>> {
+        let mut images = vec![];
+        for path in Loader::page_paths(&meta)? {
+            let image_path = if same_file(&path, meta.as_ref()) {
+                image.as_ref().to_path_buf()
+            } else {
+                Loader::resolve_image(&path, &Loader::load_meta(&path)?)?
+            };
+            images.push(Loader::load_rgba(&image_path)?);
+        }
+        Ok(images)
+    }
+
+    /// Loads the sheet and the atlas its meta names.
+    pub fn load_sheet<P: AsRef<Path>>(ctx: &mut Context, meta: P) -> GameResult<SpriteSheet> {
+        let info = Loader::load_meta(&meta)?;
+        let image = Loader::resolve_image(&meta, &info)?;
+        Loader::load_sprite_sheet(ctx, meta.as_ref(), image.as_path())
+    }
+
+    /// Loads every page of the sheet, `image` is the atlas of `meta` and the
+    /// other pages use the atlas their meta names.
     pub fn load_sprite_sheet<P: AsRef<Path>>(
         ctx: &mut Context,
         meta: P,
         image: P,
     ) -> GameResult<SpriteSheet> {
-        let info = Loader::load_meta(meta)?;
-        let frames = FrameInfo::extract_frames(&info);
-        let image = Loader::load_image(ctx, image)?;
+        let mut pages = vec![];
+        let mut images = vec![];
+        for path in Loader::page_paths(&meta)? {
+            let info = Loader::load_meta(&path)?;
+            let image_path = if same_file(&path, meta.as_ref()) {
+                image.as_ref().to_path_buf()
+            } else {
+                Loader::resolve_image(&path, &info)?
+            };
+            images.push(Rc::new(Loader::load_image(ctx, &info, &image_path)?));
+            pages.push(info);
+        }
 
         let s = SpriteSheet {
-            image: Rc::new(image),
-            info,
-            frames,
+            images,
+            info: SpriteSheetInfo::merge(&pages),
+            frames: FrameInfo::extract_pages(&pages),
+            pages,
         };
         Ok(s)
     }
 }
 
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SpriteSheet {
-    pub image: Rc<Image>,
+    /// Atlas of each page.
+    pub images: Vec<Rc<Image>>,
+    /// All pages merged, frames in page order.
     pub info: SpriteSheetInfo,
+    pub pages: Vec<SpriteSheetInfo>,
     pub frames: Vec<FrameInfo>,
 }
 
 #[derive(Debug, Clone)]
 pub struct FrameInfo {
     pub segment: ggez::graphics::Rect,
+    pub page: usize,
 }
 
 impl FrameInfo {
+    /// Frames of all pages in order, UVs relative to their own page.
+    pub fn extract_pages(pages: &[SpriteSheetInfo]) -> Vec<FrameInfo> {
+        pages
+            .iter()
+            .enumerate()
+            .flat_map(|(page, info)| {
+                FrameInfo::extract_frames(info)
+                    .into_iter()
+                    .map(move |f| FrameInfo { page, ..f })
+            })
+            .collect()
+    }
+
     pub fn extract_frames(info: &SpriteSheetInfo) -> Vec<FrameInfo> {
         let big = &info.meta;
         let Size {
@@ -89,7 +222,10 @@ impl FrameInfo {
                     h: fh / total_h,
                 };
 
-                FrameInfo { segment: src }
+                FrameInfo {
+                    segment: src,
+                    page: 0,
+                }
             })
             .collect()
     }
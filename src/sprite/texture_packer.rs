@@ -31,16 +31,67 @@ pub struct SpriteSheetMeta {
     pub size: Size,
     pub scale: String,
     pub smartupdate: String,
+    /// Colors in the atlas are multiplied by alpha.
+    #[serde(default, rename = "premultipliedAlpha")]
+    pub premultiplied_alpha: bool,
+    /// Other pages of a multi-pack sheet, relative to this meta file.
+    #[serde(default)]
+    pub related_multi_packs: Vec<String>,
 }
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use ggez::GameResult;
 use ggez::GameError;
 
+impl SpriteSheetMeta {
+    fn parse_scale(&self) -> GameResult<f32> {
+        match self.scale.trim().parse::<f32>() {
+            Ok(scale) if scale > 0.0 => Ok(scale),
+            _ => Err(GameError::ResourceLoadError(format!(
+                "meta.scale {:?} is not a positive number",
+                self.scale
+            ))),
+        }
+    }
+
+    /// Pixels per logical unit, 2 for an @2x sheet. `load_info` rejects
+    /// metas where it doesn't parse.
+    pub fn scale_factor(&self) -> f32 {
+        self.parse_scale().unwrap_or(1.0)
+    }
+}
+
 impl SpriteSheetInfo {
     pub fn load_info<P: AsRef<Path>>(path: P) -> GameResult<SpriteSheetInfo> {
-        let file = File::open(path).unwrap();
+        let file = File::open(path)?;
+
+        let info: SpriteSheetInfo = serde_json::from_reader(file)
+            .map_err(|e| GameError::ResourceLoadError(format!("{}", e)))?;
+        info.meta.parse_scale()?;
+        Ok(info)
+    }
+
+    /// Frame of the sprite in logical units, i.e. as in the @1x sheet.
+    /// Marked data stores these so all scale variants share it.
+    pub fn logical_frame(&self, sprite: &Sprite) -> Rect {
+        sprite.frame.scaled(1.0 / self.meta.scale_factor())
+    }
+
+    /// One sheet out of the pages of a multi-pack, frames in page order and
+    /// the meta of the first page.
+    pub fn merge(pages: &[SpriteSheetInfo]) -> SpriteSheetInfo {
+        SpriteSheetInfo {
+            frames: pages.iter().flat_map(|p| p.frames.iter().cloned()).collect(),
+            meta: pages[0].meta.clone(),
+        }
+    }
 
-        serde_json::from_reader(file).map_err(|e| GameError::ResourceLoadError(format!("{}", e)))
+    /// Atlas image named in the meta, relative to the meta file.
+    pub fn image_path<P: AsRef<Path>>(&self, meta_path: P) -> PathBuf {
+        meta_path
+            .as_ref()
+            .parent()
+            .map(|dir| dir.join(&self.meta.image))
+            .unwrap_or_else(|| PathBuf::from(&self.meta.image))
     }
 }
@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ggez::GameResult;
+
+use super::texture_packer::SpriteSheetInfo;
+use super::Loader;
+
+/// Name without a TexturePacker scale suffix, `level@2x` becomes `level`.
+pub fn base_name(stem: &str) -> &str {
+    if let Some(at) = stem.rfind('@') {
+        let suffix = &stem[at + 1..];
+        let number = suffix.trim_end_matches('x');
+        if suffix.ends_with('x') && !number.is_empty() && number.parse::<f32>().is_ok() {
+            return &stem[..at];
+        }
+    }
+    stem
+}
+
+fn base_of(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_string_lossy().into_owned();
+    Some(String::from(base_name(&stem)))
+}
+
+/// Metas next to `meta` that are scale variants of the same sheet, e.g.
+/// `level.json` and `level@2x.json`, including `meta` itself.
+pub fn scale_variants<P: AsRef<Path>>(meta: P) -> Vec<PathBuf> {
+    let meta = meta.as_ref();
+    let base = base_of(meta);
+    let dir = meta.parent()
+        .map(|d| d.to_path_buf())
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let mut variants: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|rd| rd.filter_map(|e| e.ok().map(|e| e.path())).collect())
+        .unwrap_or_else(|_| vec![])
+        .into_iter()
+        .filter(|p: &PathBuf| p.extension().map(|e| e == "json").unwrap_or(false))
+        .filter(|p| base_of(p) == base)
+        .collect();
+    if variants.is_empty() {
+        variants.push(meta.to_path_buf());
+    }
+    variants.sort();
+    variants
+}
+
+fn label(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Every scale variant of the sheet with its pages merged, labelled by file
+/// name. `meta` comes first.
+pub fn load_variants<P: AsRef<Path>>(meta: P) -> GameResult<Vec<(String, SpriteSheetInfo)>> {
+    let meta = meta.as_ref();
+    let mut variants = vec![(label(meta), SpriteSheetInfo::merge(&Loader::load_pages(meta)?))];
+    for path in scale_variants(meta) {
+        if path.file_name() != meta.file_name() {
+            let info = SpriteSheetInfo::merge(&Loader::load_pages(&path)?);
+            variants.push((label(&path), info));
+        }
+    }
+    Ok(variants)
+}
@@ -0,0 +1,399 @@
+use ggez::event::Keycode;
+use ggez::graphics;
+use ggez::graphics::*;
+use ggez::{timer, Context, GameResult};
+use std::rc::Rc;
+
+use super::super::marker::*;
+use super::super::Assets;
+use super::widgets::*;
+use super::{BUTTON_SIZE, SPACING};
+
+/// The panel covers the sprite grid.
+const PANEL: (f32, f32, f32, f32) = (600.0, 500.0, 1180.0, 960.0);
+const LIST_ROW: (f32, f32) = (260.0, 30.0);
+const LIST_ROWS: usize = 24;
+const FRAME_SIZE: (f32, f32) = (110.0, 30.0);
+const FRAME_COLUMNS: usize = 7;
+const STRIP_FRAMES: usize = 28;
+/// Largest side of the played sprite.
+const PREVIEW_SIZE: f32 = 400.0;
+/// Milliseconds added or taken by the duration buttons.
+const DURATION_STEP: u32 = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnimationsMsg {
+    Select(usize),
+    /// Adds an animation for every numbered frame sequence without one.
+    AutoGroup,
+    /// New animation of the multi-selected sprites, in the order they were picked.
+    FromSelection,
+    Delete,
+    Mode(LoopMode),
+    Frame(usize),
+    Slower,
+    Faster,
+    /// Gives every frame the duration of the selected one.
+    SameDuration,
+    Rename(String),
+    Close,
+}
+
+/// Name like `base` that no animation uses yet.
+fn unique_name(base: &str, animations: &[Animation]) -> String {
+    let taken = |name: &str| animations.iter().any(|a| a.name == name);
+    let mut name = String::from(base);
+    let mut n = 2;
+    while taken(&name) {
+        name = format!("{} {}", base, n);
+        n += 1;
+    }
+    name
+}
+
+/// Lists the sheet's animations, edits the selected one and plays it.
+/// The animations themselves belong to `Game`, the panel only keeps what
+/// is selected and how long it has been playing.
+pub struct AnimationsUi {
+    assets: Rc<Assets>,
+    current: Option<usize>,
+    frame: usize,
+    /// Seconds since the preview started over.
+    elapsed: f64,
+    toolbar: Container<AnimationsMsg>,
+    name_input: TextInput<AnimationsMsg>,
+    modes: Container<AnimationsMsg>,
+    durations: Container<AnimationsMsg>,
+    list: Container<AnimationsMsg>,
+    strip: Container<AnimationsMsg>,
+    needs_rebuild: bool,
+}
+
+impl AnimationsUi {
+    pub fn new(
+        ctx: &mut Context,
+        assets: Rc<Assets>,
+        animations: &[Animation],
+    ) -> GameResult<AnimationsUi> {
+        let (x, y) = (PANEL.0, PANEL.1 - PANEL.3 / 2.0);
+        let right = x + 145.0;
+        let font = &assets.font;
+
+        let button = |ctx: &mut Context, text: &str, msg: AnimationsMsg| {
+            Button::new(ctx, text, font, BUTTON_SIZE, msg)
+        };
+        let mut toolbar = Container::horizontal(SPACING)
+            .with(button(ctx, "Auto-group", AnimationsMsg::AutoGroup)?)
+            .with(button(ctx, "From sel.", AnimationsMsg::FromSelection)?)
+            .with(button(ctx, "Delete", AnimationsMsg::Delete)?)
+            .with(button(ctx, "Close", AnimationsMsg::Close)?);
+        toolbar.layout(Point::new(x, y + 40.0));
+
+        let mut name_input = TextInput::new(
+            assets.clone(),
+            500.0,
+            "Animation name, Enter renames",
+            Box::new(|value: &str| AnimationsMsg::Rename(String::from(value))),
+        );
+        name_input.layout(Point::new(right, y + 110.0));
+
+        let toggle = |ctx: &mut Context, text: &str, mode: LoopMode| {
+            Button::new(ctx, text, font, BUTTON_SIZE, AnimationsMsg::Mode(mode))
+                .map(|button| ToggleButton::new(button, false))
+        };
+        let mut modes = Container::horizontal(SPACING)
+            .with(toggle(ctx, "Loop", LoopMode::Loop)?)
+            .with(toggle(ctx, "Once", LoopMode::Once)?)
+            .with(toggle(ctx, "Ping-pong", LoopMode::PingPong)?);
+        modes.layout(Point::new(right, y + 165.0));
+
+        let mut durations = Container::horizontal(SPACING)
+            .with(button(ctx, "-10ms", AnimationsMsg::Faster)?)
+            .with(button(ctx, "+10ms", AnimationsMsg::Slower)?)
+            .with(button(ctx, "All frames", AnimationsMsg::SameDuration)?);
+        durations.layout(Point::new(right, y + PANEL.3 - 60.0));
+
+        let mut ui = AnimationsUi {
+            assets: assets.clone(),
+            current: None,
+            frame: 0,
+            elapsed: 0.0,
+            toolbar,
+            name_input,
+            modes,
+            durations,
+            list: Container::vertical(SPACING),
+            strip: Container::grid(FRAME_COLUMNS, SPACING),
+            needs_rebuild: true,
+        };
+        ui.select(if animations.is_empty() { None } else { Some(0) }, animations);
+        ui.rebuild(ctx, animations)?;
+        Ok(ui)
+    }
+
+    fn select(&mut self, ix: Option<usize>, animations: &[Animation]) {
+        self.current = ix;
+        self.frame = 0;
+        self.elapsed = 0.0;
+        let name = ix.and_then(|ix| animations.get(ix)).map(|a| a.name.as_str());
+        self.name_input.set_value(name.unwrap_or(""));
+        self.needs_rebuild = true;
+    }
+
+    fn rebuild(&mut self, ctx: &mut Context, animations: &[Animation]) -> GameResult<()> {
+        let font = &self.assets.font;
+        let top = PANEL.1 - PANEL.3 / 2.0 + 80.0;
+
+        self.list = Container::vertical(SPACING);
+        for (ix, animation) in animations.iter().enumerate().take(LIST_ROWS) {
+            let label = format!("{} ({})", animation.name, animation.frames.len());
+            let button = Button::new(ctx, &label, font, LIST_ROW, AnimationsMsg::Select(ix))?;
+            self.list.push(ToggleButton::new(button, false));
+        }
+        let (_, h) = self.list.size();
+        self.list.layout(Point::new(PANEL.0 - PANEL.2 / 2.0 + 150.0, top + h / 2.0));
+
+        self.strip = Container::grid(FRAME_COLUMNS, SPACING);
+        if let Some(animation) = self.current.and_then(|ix| animations.get(ix)) {
+            for (ix, frame) in animation.frames.iter().enumerate().take(STRIP_FRAMES) {
+                let label = format!("{}: {}ms", ix + 1, frame.duration);
+                let msg = AnimationsMsg::Frame(ix);
+                let button = Button::new(ctx, &label, font, FRAME_SIZE, msg)?;
+                self.strip.push(ToggleButton::new(button, false));
+            }
+        }
+        let (_, h) = self.strip.size();
+        self.strip.layout(Point::new(PANEL.0 + 145.0, top + 580.0 + h / 2.0));
+
+        self.sync(animations);
+        self.needs_rebuild = false;
+        Ok(())
+    }
+
+    fn sync(&mut self, animations: &[Animation]) {
+        let (current, frame) = (self.current, self.frame);
+        let mode = current.and_then(|ix| animations.get(ix)).map(|a| a.loop_mode);
+        let is_on = |msg: &AnimationsMsg| match *msg {
+            AnimationsMsg::Select(ix) => Some(ix) == current,
+            AnimationsMsg::Mode(m) => Some(m) == mode,
+            AnimationsMsg::Frame(ix) => ix == frame,
+            _ => false,
+        };
+        self.list.sync(&is_on);
+        self.modes.sync(&is_on);
+        self.strip.sync(&is_on);
+    }
+
+    /// Advances the preview and rebuilds the widgets if the animations
+    /// changed, needs the context for text.
+    pub fn update(&mut self, ctx: &mut Context, animations: &[Animation]) -> GameResult<()> {
+        self.elapsed += timer::duration_to_f64(timer::get_delta(ctx));
+        if self.needs_rebuild {
+            self.rebuild(ctx, animations)?;
+        }
+        Ok(())
+    }
+
+    /// Changes the duration of the selected frame.
+    fn set_duration<F>(&mut self, current: Option<usize>, animations: &mut [Animation], change: F)
+    where
+        F: Fn(u32) -> u32,
+    {
+        let frame = self.frame;
+        if let Some(frame) = current.and_then(|ix| animations[ix].frames.get_mut(frame)) {
+            frame.duration = change(frame.duration);
+            self.needs_rebuild = true;
+        }
+    }
+
+    /// Returns whether the panel wants to be closed.
+    fn handle(
+        &mut self,
+        msg: AnimationsMsg,
+        animations: &mut Vec<Animation>,
+        marked: &[SpriteData],
+        selection: &[usize],
+    ) -> bool {
+        let current = self.current.filter(|&ix| ix < animations.len());
+        match msg {
+            AnimationsMsg::Select(ix) => self.select(Some(ix), animations),
+            AnimationsMsg::AutoGroup => {
+                add_missing(animations, auto_group(marked));
+                if current.is_none() && !animations.is_empty() {
+                    self.select(Some(0), animations);
+                }
+                self.needs_rebuild = true;
+            }
+            AnimationsMsg::FromSelection => {
+                let sprites: Vec<String> = selection
+                    .iter()
+                    .filter_map(|&ix| marked.get(ix))
+                    .map(|sd| sd.name.clone())
+                    .collect();
+                if let Some(first) = sprites.first().cloned() {
+                    let base = sequence_name(&first)
+                        .map(|(base, _)| base)
+                        .unwrap_or_else(|| String::from("animation"));
+                    let name = unique_name(&base, animations);
+                    animations.push(Animation::new(&name, sprites));
+                    let last = animations.len() - 1;
+                    self.select(Some(last), animations);
+                }
+            }
+            AnimationsMsg::Delete => if let Some(ix) = current {
+                animations.remove(ix);
+                let next = if animations.is_empty() {
+                    None
+                } else {
+                    Some(ix.min(animations.len() - 1))
+                };
+                self.select(next, animations);
+            },
+            AnimationsMsg::Mode(mode) => if let Some(ix) = current {
+                animations[ix].loop_mode = mode;
+                self.elapsed = 0.0;
+                self.sync(animations);
+            },
+            AnimationsMsg::Frame(frame) => {
+                self.frame = frame;
+                self.sync(animations);
+            }
+            AnimationsMsg::Slower => self.set_duration(current, animations, |d| d + DURATION_STEP),
+            AnimationsMsg::Faster => self.set_duration(current, animations, |d| {
+                d.saturating_sub(DURATION_STEP).max(DURATION_STEP)
+            }),
+            AnimationsMsg::SameDuration => if let Some(ix) = current {
+                let animation = &mut animations[ix];
+                if let Some(duration) = animation.frames.get(self.frame).map(|f| f.duration) {
+                    for frame in animation.frames.iter_mut() {
+                        frame.duration = duration;
+                    }
+                    self.needs_rebuild = true;
+                }
+            },
+            AnimationsMsg::Rename(name) => if let Some(ix) = current {
+                let name = name.trim();
+                let taken = animations
+                    .iter()
+                    .enumerate()
+                    .any(|(other, a)| other != ix && a.name == name);
+                if !name.is_empty() && !taken {
+                    animations[ix].name = String::from(name);
+                    self.needs_rebuild = true;
+                }
+                self.name_input.set_value(&animations[ix].name);
+            },
+            AnimationsMsg::Close => return true,
+        };
+        false
+    }
+
+    pub fn hover(&mut self, point: &Point) -> Option<Rect> {
+        let widgets = [
+            self.toolbar.hover(point),
+            self.name_input.hover(point),
+            self.modes.hover(point),
+            self.durations.hover(point),
+            self.list.hover(point),
+            self.strip.hover(point),
+        ];
+        widgets.iter().fold(None, |found, w| found.or(*w))
+    }
+
+    /// Handles a click, returns whether the panel wants to be closed.
+    pub fn interact(
+        &mut self,
+        point: &Point,
+        animations: &mut Vec<Animation>,
+        marked: &[SpriteData],
+        selection: &[usize],
+    ) -> bool {
+        let msg = self.name_input
+            .click(point)
+            .or_else(|| self.toolbar.click(point))
+            .or_else(|| self.modes.click(point))
+            .or_else(|| self.durations.click(point))
+            .or_else(|| self.list.click(point))
+            .or_else(|| self.strip.click(point));
+        match msg {
+            Some(msg) => self.handle(msg, animations, marked, selection),
+            None => false,
+        }
+    }
+
+    /// Returns whether the panel wants to be closed.
+    pub fn key(
+        &mut self,
+        keycode: Keycode,
+        shift: bool,
+        animations: &mut Vec<Animation>,
+        marked: &[SpriteData],
+    ) -> bool {
+        // Escape unfocuses the name first, a second one closes the panel.
+        if keycode == Keycode::Escape && !self.name_input.is_focused() {
+            return true;
+        }
+        match self.name_input.key(keycode, shift) {
+            Some(msg) => self.handle(msg, animations, marked, &[]),
+            None => false,
+        }
+    }
+
+    fn draw_preview(
+        &self,
+        ctx: &mut Context,
+        animation: &Animation,
+        marked: &[SpriteData],
+        images: &[Rc<Image>],
+    ) -> GameResult<()> {
+        let shown = animation
+            .frame_at((self.elapsed * 1000.0) as u32)
+            .map(|ix| &animation.frames[ix])
+            .and_then(|f| marked.iter().find(|sd| sd.name == f.sprite));
+        let sd = match shown {
+            Some(sd) => sd,
+            None => return Ok(()),
+        };
+
+        if let Some(image) = images.get(sd.page) {
+            let uv = &sd.on_screen_frame;
+            let (w, h) = (uv.w * image.width() as f32, uv.h * image.height() as f32);
+            let scale = PREVIEW_SIZE / w.max(h);
+            let param = DrawParam {
+                src: Rect::from(uv.clone()),
+                dest: Point::new(PANEL.0 + 145.0, PANEL.1 - 70.0),
+                scale: Point::new(scale, scale),
+                offset: Point::zero(),
+                ..Default::default()
+            };
+            graphics::draw_ex(ctx, &**image, param)?;
+        }
+        Ok(())
+    }
+
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        animations: &[Animation],
+        marked: &[SpriteData],
+        images: &[Rc<Image>],
+    ) -> GameResult<()> {
+        let panel = Rect::new(PANEL.0, PANEL.1, PANEL.2, PANEL.3);
+        graphics::set_color(ctx, Color::new(0.1, 0.1, 0.1, 1.0))?;
+        graphics::rectangle(ctx, DrawMode::Fill, panel)?;
+        graphics::set_color(ctx, graphics::WHITE)?;
+        graphics::rectangle(ctx, DrawMode::Line, panel)?;
+
+        self.toolbar.draw(ctx)?;
+        self.list.draw(ctx)?;
+
+        if let Some(animation) = self.current.and_then(|ix| animations.get(ix)) {
+            self.name_input.draw(ctx)?;
+            self.modes.draw(ctx)?;
+            self.strip.draw(ctx)?;
+            self.durations.draw(ctx)?;
+            self.draw_preview(ctx, animation, marked, images)?;
+        }
+        Ok(())
+    }
+}
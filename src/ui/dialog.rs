@@ -0,0 +1,64 @@
+use ggez::graphics::*;
+use ggez::graphics;
+use ggez::{Context, GameResult};
+use std::rc::Rc;
+
+use super::super::Assets;
+use super::widgets::*;
+use super::{BUTTON_SIZE, SPACING};
+
+/// Big enough to dim the whole window around the dialog.
+const SHADE_SIZE: f32 = 4000.0;
+
+/// Modal question with a row of answers, drawn over everything else.
+pub struct Dialog<T> {
+    rect: Rect,
+    content: Container<T>,
+}
+
+impl<T: Clone + 'static> Dialog<T> {
+    pub fn new(
+        ctx: &mut Context,
+        assets: Rc<Assets>,
+        message: &str,
+        center: Point,
+        choices: Vec<(&str, T)>,
+    ) -> GameResult<Dialog<T>> {
+        let mut answers = Container::horizontal(SPACING);
+        for (label, choice) in choices.into_iter() {
+            answers.push(Button::new(ctx, label, &assets.font, BUTTON_SIZE, choice)?);
+        }
+
+        let mut content = Container::vertical(40.0)
+            .with(Label::new(ctx, message, &assets.font)?)
+            .with(answers);
+        content.layout(center);
+
+        let (w, h) = content.size();
+        let rect = Rect::new(center.x, center.y, w.max(360.0) + 60.0, h + 60.0);
+
+        Ok(Dialog { rect, content })
+    }
+
+    pub fn hover(&mut self, point: &Point) -> Option<Rect> {
+        self.content.hover(point)
+    }
+
+    /// The answer, if a button was clicked.
+    pub fn interact(&mut self, point: &Point) -> Option<T> {
+        self.content.click(point)
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let shade = Rect::new(self.rect.x, self.rect.y, SHADE_SIZE, SHADE_SIZE);
+        graphics::set_color(ctx, Color::new(0.0, 0.0, 0.0, 0.7))?;
+        graphics::rectangle(ctx, DrawMode::Fill, shade)?;
+
+        graphics::set_color(ctx, Color::new(0.15, 0.15, 0.15, 1.0))?;
+        graphics::rectangle(ctx, DrawMode::Fill, self.rect)?;
+        graphics::set_color(ctx, graphics::WHITE)?;
+        graphics::rectangle(ctx, DrawMode::Line, self.rect)?;
+
+        self.content.draw(ctx)
+    }
+}
@@ -0,0 +1,329 @@
+use ggez::event::Keycode;
+use ggez::graphics;
+use ggez::graphics::*;
+use ggez::{Context, GameResult};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::super::sprite::texture_packer::SpriteSheetInfo;
+use super::super::sprite::{atlas, Loader};
+use super::super::Assets;
+use super::widgets::*;
+use super::SPACING;
+
+const ROW_SIZE: (f32, f32) = (860.0, 30.0);
+const VISIBLE_ROWS: usize = 18;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BrowserMsg {
+    Open(PathBuf),
+    /// A recent sheet, remembered together with its image.
+    Pick(PathBuf, PathBuf),
+    Up,
+    Filter(String),
+    Cancel,
+}
+
+/// What the browser wants the app to do after an interaction.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BrowserResult {
+    /// A sheet meta file together with the atlas image it names.
+    Picked(PathBuf, PathBuf),
+    Cancelled,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    label: String,
+    msg: BrowserMsg,
+    is_dir: bool,
+}
+
+/// Meta and image pairs of the sheet metas among `paths`.
+fn sheet_pairs(paths: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+    paths
+        .iter()
+        .filter(|p| p.extension().map(|e| e == "json").unwrap_or(false))
+        .filter_map(|p| {
+            let info = SpriteSheetInfo::load_info(p).ok()?;
+            let image = Loader::resolve_image(p, &info).ok()?;
+            Some((p.clone(), image))
+        })
+        .collect()
+}
+
+/// The pair `path` is the meta or the atlas image of.
+fn find_pair(path: &Path, pairs: &[(PathBuf, PathBuf)]) -> Option<(PathBuf, PathBuf)> {
+    pairs
+        .iter()
+        .find(|&&(ref meta, ref image)| {
+            meta.as_path() == path || image.file_name() == path.file_name()
+        })
+        .cloned()
+}
+
+fn list_dir(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|rd| rd.filter_map(|e| e.ok().map(|e| e.path())).collect())
+        .unwrap_or_else(|_| vec![])
+}
+
+/// Meta and image pair for a `.json` sheet or its atlas image.
+pub fn sheet_pair(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    match ext.as_ref().map(|e| e.as_str()) {
+        Some("json") => find_pair(path, &sheet_pairs(&[path.to_path_buf()])),
+        Some(ext) if atlas::EXTENSIONS.contains(&ext) => {
+            find_pair(path, &sheet_pairs(&list_dir(path.parent()?)))
+        }
+        _ => None,
+    }
+}
+
+/// File browser drawn inside the window: lists directories and sheet files,
+/// pairing every meta `.json` with the image named in it.
+pub struct FileBrowser {
+    assets: Rc<Assets>,
+    center: Point,
+    dir: PathBuf,
+    filter: String,
+    recent: Vec<(PathBuf, PathBuf)>,
+    entries: Vec<Entry>,
+    first_row: usize,
+    title: Container<BrowserMsg>,
+    filter_input: TextInput<BrowserMsg>,
+    list: Container<BrowserMsg>,
+    footer: Container<BrowserMsg>,
+    needs_rebuild: bool,
+}
+
+impl FileBrowser {
+    pub fn new(
+        ctx: &mut Context,
+        assets: Rc<Assets>,
+        center: Point,
+        dir: PathBuf,
+        recent: Vec<(PathBuf, PathBuf)>,
+    ) -> GameResult<FileBrowser> {
+        let filter_input = TextInput::new(
+            assets.clone(),
+            ROW_SIZE.0,
+            "Click to filter, Enter to apply",
+            Box::new(|value: &str| BrowserMsg::Filter(String::from(value))),
+        );
+        let mut browser = FileBrowser {
+            assets,
+            center,
+            dir,
+            filter: String::new(),
+            recent,
+            entries: vec![],
+            first_row: 0,
+            title: Container::vertical(SPACING),
+            filter_input,
+            list: Container::vertical(SPACING),
+            footer: Container::horizontal(SPACING),
+            needs_rebuild: true,
+        };
+        browser.read_dir();
+        browser.rebuild(ctx)?;
+        Ok(browser)
+    }
+
+    fn read_dir(&mut self) {
+        let filter = self.filter.to_lowercase();
+        let paths = list_dir(&self.dir);
+        // Every meta is parsed once, atlases are matched against the pairs.
+        let pairs = sheet_pairs(&paths);
+        let mut entries: Vec<Entry> = paths
+            .into_iter()
+            .filter_map(|path: PathBuf| {
+                let name = path.file_name()?.to_string_lossy().into_owned();
+                if name.starts_with('.') || !name.to_lowercase().contains(&filter) {
+                    return None;
+                }
+                if path.is_dir() {
+                    return Some(Entry {
+                        label: format!("{}/", name),
+                        msg: BrowserMsg::Open(path),
+                        is_dir: true,
+                    });
+                }
+                let ext = path.extension()?.to_string_lossy().to_lowercase();
+                if ext != "json" && !atlas::EXTENSIONS.contains(&ext.as_str()) {
+                    return None;
+                }
+                let label = match find_pair(&path, &pairs) {
+                    Some((_, ref image)) if ext == "json" => format!(
+                        "{}  ->  {}",
+                        name,
+                        image.file_name().unwrap().to_string_lossy()
+                    ),
+                    Some((ref meta, _)) => format!(
+                        "{}  <-  {}",
+                        name,
+                        meta.file_name().unwrap().to_string_lossy()
+                    ),
+                    None => format!("{}  (not a sheet)", name),
+                };
+                Some(Entry {
+                    label,
+                    msg: BrowserMsg::Open(path),
+                    is_dir: false,
+                })
+            })
+            .collect();
+
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.label.cmp(&b.label)));
+
+        let recent = self.recent.iter().filter_map(|&(ref meta, ref image)| {
+            let label = format!("recent: {}", meta.display());
+            if label.to_lowercase().contains(&filter) {
+                Some(Entry {
+                    label,
+                    msg: BrowserMsg::Pick(meta.clone(), image.clone()),
+                    is_dir: false,
+                })
+            } else {
+                None
+            }
+        });
+        self.entries = recent.chain(entries).collect();
+        self.first_row = 0;
+        self.needs_rebuild = true;
+    }
+
+    fn rebuild(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let font = &self.assets.font;
+
+        self.title = Container::vertical(SPACING).with(Label::new(
+            ctx,
+            &format!("{}", self.dir.display()),
+            font,
+        )?);
+
+        self.list = Container::vertical(SPACING);
+        for entry in self.entries.iter().skip(self.first_row).take(VISIBLE_ROWS) {
+            let msg = entry.msg.clone();
+            self.list
+                .push(Button::new(ctx, &entry.label, font, ROW_SIZE, msg)?);
+        }
+
+        self.footer = Container::horizontal(SPACING)
+            .with(Button::new(ctx, "Up", font, (115.0, 45.0), BrowserMsg::Up)?)
+            .with(Button::new(
+                ctx,
+                "Cancel",
+                font,
+                (115.0, 45.0),
+                BrowserMsg::Cancel,
+            )?);
+
+        let list_h = VISIBLE_ROWS as f32 * (ROW_SIZE.1 + SPACING);
+        let top = self.center.y - list_h / 2.0;
+        self.title.layout(Point::new(self.center.x, top - 70.0));
+        self.filter_input
+            .layout(Point::new(self.center.x, top - 30.0));
+        let (_, h) = self.list.size();
+        self.list.layout(Point::new(
+            self.center.x,
+            self.center.y - (list_h - h) / 2.0,
+        ));
+        self.footer.layout(Point::new(
+            self.center.x,
+            self.center.y + list_h / 2.0 + 40.0,
+        ));
+
+        self.needs_rebuild = false;
+        Ok(())
+    }
+
+    /// Rebuilds the widgets if the listing changed, needs the context for text.
+    pub fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        if self.needs_rebuild {
+            self.rebuild(ctx)?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, msg: BrowserMsg) -> Option<BrowserResult> {
+        match msg {
+            BrowserMsg::Open(path) => {
+                if path.is_dir() {
+                    self.dir = path;
+                    self.read_dir();
+                    None
+                } else {
+                    sheet_pair(&path).map(|(meta, image)| BrowserResult::Picked(meta, image))
+                }
+            }
+            BrowserMsg::Pick(meta, image) => Some(BrowserResult::Picked(meta, image)),
+            BrowserMsg::Up => {
+                if let Some(parent) = self.dir.parent().map(|p| p.to_path_buf()) {
+                    self.dir = parent;
+                    self.read_dir();
+                }
+                None
+            }
+            BrowserMsg::Filter(filter) => {
+                self.filter = filter;
+                self.read_dir();
+                None
+            }
+            BrowserMsg::Cancel => Some(BrowserResult::Cancelled),
+        }
+    }
+
+    pub fn hover(&mut self, point: &Point) -> Option<Rect> {
+        let filter = self.filter_input.hover(point);
+        let list = self.list.hover(point);
+        let footer = self.footer.hover(point);
+        filter.or(list).or(footer)
+    }
+
+    pub fn interact(&mut self, point: &Point) -> Option<BrowserResult> {
+        let filter = self.filter_input.click(point);
+        let msg = filter
+            .or_else(|| self.list.click(point))
+            .or_else(|| self.footer.click(point));
+        msg.and_then(|m| self.handle(m))
+    }
+
+    pub fn key(&mut self, keycode: Keycode, shift: bool) -> Option<BrowserResult> {
+        // Escape unfocuses the filter first, a second one closes the browser.
+        if keycode == Keycode::Escape && !self.filter_input.is_focused() {
+            return Some(BrowserResult::Cancelled);
+        }
+        self.filter_input
+            .key(keycode, shift)
+            .and_then(|m| self.handle(m))
+    }
+
+    /// Mouse wheel, 1 up and -1 down like ggez reports it.
+    pub fn scroll(&mut self, y: i32) {
+        let max = self.entries.len().saturating_sub(VISIBLE_ROWS);
+        let row = self.first_row as i32 - y * 3;
+        self.first_row = (row.max(0) as usize).min(max);
+        self.needs_rebuild = true;
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let list_h = VISIBLE_ROWS as f32 * (ROW_SIZE.1 + SPACING);
+        let panel = Rect::new(
+            self.center.x,
+            self.center.y,
+            ROW_SIZE.0 + 40.0,
+            list_h + 220.0,
+        );
+        graphics::set_color(ctx, Color::new(0.1, 0.1, 0.1, 1.0))?;
+        graphics::rectangle(ctx, DrawMode::Fill, panel)?;
+        graphics::set_color(ctx, graphics::WHITE)?;
+        graphics::rectangle(ctx, DrawMode::Line, panel)?;
+
+        self.title.draw(ctx)?;
+        self.filter_input.draw(ctx)?;
+        self.list.draw(ctx)?;
+        self.footer.draw(ctx)
+    }
+}
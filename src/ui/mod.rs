@@ -1,154 +1,138 @@
-
 use ggez::graphics::*;
 use ggez::graphics;
 use ggez::{Context, GameResult};
 use super::Assets;
 use super::marker::*;
-use std::collections::HashSet;
-use std::cmp::PartialEq;
-use std::hash::Hash;
+use super::sprite::geom;
 use std::rc::Rc;
-use std::borrow::Borrow;
-use ggez::GameError;
-use serde_json;
 use std::collections::HashMap;
-use std::cell::RefCell;
-
-const H_SPACE: f32 = 120.0;
-const W_SPACE: f32 = 50.0;
-
-const W_SIZE: f32 = 115.0;
-const H_SIZE: f32 = 45.0;
 
-type SBST = SimpleButton<SpriteType>;
-
-pub struct SimpleButton<T> {
-    pub rect: Rect,
-    pub text: Text,
-    pub rotation: f32,
-    pub offset: Point,
-    pub action: Box<(FnMut(&mut T) -> ())>,
+mod problems;
+mod dialog;
+mod animations;
+mod file_browser;
+mod nine_slice;
+mod tags;
+pub mod widgets;
+
+pub use self::problems::ProblemsUi;
+pub use self::dialog::Dialog;
+pub use self::animations::AnimationsUi;
+pub use self::file_browser::{sheet_pair, BrowserResult, FileBrowser};
+pub use self::nine_slice::{Guide, NineSliceUi};
+pub use self::tags::{TagsMsg, TagsUi};
+
+use self::widgets::*;
+
+const BUTTON_SIZE: (f32, f32) = (115.0, 45.0);
+/// Narrower than `BUTTON_SIZE`, all marker categories fit in a row.
+const KIND_SIZE: (f32, f32) = (90.0, 45.0);
+const ICON_SIZE: (f32, f32) = (60.0, 60.0);
+const SPACING: f32 = 5.0;
+
+/// App-level behaviour a panel asks for, handled by `Game`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UiAction {
+    Save,
+    /// Drop the unapplied edits of the selected sprite.
+    Revert,
+    Next,
+    Previous,
+    /// Copy the selected sprite's markers to every sprite in the multi-selection.
+    ApplyToSelection,
+    /// Show the file browser to switch to another sheet.
+    Open,
+    /// Show the animations panel.
+    Animations,
+    /// Show the tag editor.
+    Tags,
 }
 
-impl<T> PartialEq for SimpleButton<T> {
-    fn eq(&self, other: &SimpleButton<T>) -> bool {
-        self.rect == other.rect && self.text.contents() == other.text.contents()
-    }
+/// Pivot positions to snap to, relative to the sprite's source size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    Center,
+    BottomCenter,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
 }
 
-impl<T> SimpleButton<T> {
-    pub fn new33(
-        ctx: &mut Context,
-        assets: Rc<Assets>,
-        text: &str,
-        position: usize,
-        offset: &Point,
-        action: Box<FnMut(&mut T) -> ()>,
-    ) -> SimpleButton<T> {
-        let v = (position as isize - 1) / 3;
-        let h = (position as isize - 1) % 3;
-
-        let text = Text::new(ctx, text, &assets.font).unwrap();
-        let rect = Rect::new(
-            H_SPACE * (h - 1) as f32 + offset.x,
-            W_SPACE * (v - 1) as f32 + offset.y,
-            W_SIZE,
-            H_SIZE,
-        );
-
-        SimpleButton::<T> {
-            rect,
-            text,
-            rotation: 0.0,
-            offset: offset.clone(),
-            action: action,
-        }
+impl Anchor {
+    pub fn all() -> [Anchor; 6] {
+        [
+            Anchor::Center,
+            Anchor::BottomCenter,
+            Anchor::TopLeft,
+            Anchor::TopRight,
+            Anchor::BottomLeft,
+            Anchor::BottomRight,
+        ]
     }
 
-    pub fn new55(
-        ctx: &mut Context,
-        assets: Rc<Assets>,
-        text: &str,
-        position: isize,
-        rotation: f32,
-        offset: &Point,
-        action: Box<FnMut(&mut T) -> ()>,
-    ) -> SimpleButton<T> {
-        let v = (position - 1) / 5;
-        let h = (position - 1) % 5;
-
-        let space = 65.0;
-        let size = 60.0;
-
-        let text = Text::new(ctx, text, &assets.awesome).unwrap();
-        let rect = Rect::new(
-            space * (h - 2) as f32 + offset.x,
-            space * (v - 2) as f32 + offset.y,
-            size,
-            size,
-        );
-
-        SimpleButton::<T> {
-            rect,
-            text,
-            rotation,
-            offset: offset.clone(),
-            action,
-        }
-    }
-
-    pub fn interact(&mut self, point: &Point, val: &mut T) -> bool {
-        let yes = point_within(point, &self.rect);
-        if yes {
-            (*self.action)(val)
+    pub fn pivot(&self) -> geom::Point {
+        let (x, y) = match *self {
+            Anchor::Center => (0.5, 0.5),
+            Anchor::BottomCenter => (0.5, 1.0),
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
         };
-        yes
-    }
-
-    pub fn hover(&self, point: &Point) -> bool {
-        point_within(point, &self.rect)
+        geom::Point { x, y }
     }
 
-    pub fn draw(&self, ctx: &mut Context) {
-        let center = center(&self.rect);
-        graphics::rectangle(ctx, DrawMode::Line, self.rect.clone()).unwrap();
-        graphics::draw(ctx, &self.text, center, self.rotation).unwrap();
+    fn label(&self) -> &'static str {
+        match *self {
+            Anchor::Center => "C",
+            Anchor::BottomCenter => "B",
+            Anchor::TopLeft => "TL",
+            Anchor::TopRight => "TR",
+            Anchor::BottomLeft => "BL",
+            Anchor::BottomRight => "BR",
+        }
     }
 }
 
 pub trait UiState {
     fn draw(&self, ctx: &mut Context);
-    fn interact(&mut self, ctx: &mut Context, point: &Point) -> GameResult<()>;
+    fn interact(&mut self, ctx: &mut Context, point: &Point) -> GameResult<Option<UiAction>>;
     fn hover(&mut self, point: &Point) -> Option<Rect>;
     fn return_state(&self) -> Option<SpriteType>;
-}
 
-type SBAT = SimpleButton<SubUiContainer>;
-
-type SubUi = Rc<RefCell<Box<UiState>>>;
+    /// Replaces the edited markers, for edits made outside the panel.
+    fn set_state(&mut self, _state: SpriteType) {}
+}
 
-pub struct AssetUiButtons {
-    object: SBAT,
-    platform: SBAT,
-    ground: SBAT,
-    save: SBAT,
+fn toggle<M: Clone>(
+    ctx: &mut Context,
+    assets: &Assets,
+    text: &str,
+    size: (f32, f32),
+    message: M,
+) -> GameResult<ToggleButton<M>> {
+    let button = Button::new(ctx, text, &assets.font, size, message)?;
+    Ok(ToggleButton::new(button, false))
 }
 
-pub struct SubUiContainer {
-    sub_ui: SubUi,
-    object_ui: SubUi,
-    platform_ui: SubUi,
-    ground_ui: SubUi,
+fn toggle_in<T: PartialEq>(state: &mut Vec<T>, value: T) {
+    if state.contains(&value) {
+        state.retain(|i| *i != value);
+    } else {
+        state.push(value);
+    }
 }
 
 pub struct AssetTypeUi {
     assets: Rc<Assets>,
     offset: Point,
     state: Option<SpriteData>,
-    hovered: Option<Rect>,
-    selected: Option<Rect>,
-    sub_ui_container: SubUiContainer,
-    buttons: AssetUiButtons,
+    kind: Option<SpriteKind>,
+    kinds: Container<SpriteKind>,
+    anchors: Container<Anchor>,
+    actions: Container<UiAction>,
+    sub_uis: HashMap<SpriteKind, Box<UiState>>,
 }
 
 impl AssetTypeUi {
@@ -157,134 +141,126 @@ impl AssetTypeUi {
         assets: Rc<Assets>,
         offset: Point,
         data: Option<&SpriteData>,
+        image: Option<Rc<Image>>,
     ) -> GameResult<AssetTypeUi> {
-        let ui = AssetTypeUi::build_sub_ui(data.map(|d| &d.markers), &offset, ctx, assets.clone())?;
-        let op = &offset;
+        let markers = data.map(|d| d.markers.clone());
+        let kind = markers.as_ref().map(|m| m.kind());
+
+        let mut kinds = Container::horizontal(SPACING)
+            .with(toggle(ctx, &assets, "Object", KIND_SIZE, SpriteKind::Object)?)
+            .with(toggle(ctx, &assets, "Platform", KIND_SIZE, SpriteKind::Platform)?)
+            .with(toggle(ctx, &assets, "Ground", KIND_SIZE, SpriteKind::Ground)?)
+            .with(toggle(ctx, &assets, "9-slice", KIND_SIZE, SpriteKind::NineSlice)?);
+        kinds.layout(offset);
+        kinds.sync(&|k| Some(*k) == kind);
+
+        let mut anchors = Container::horizontal(SPACING);
+        for anchor in Anchor::all().iter() {
+            let button = Button::new(ctx, anchor.label(), &assets.font, (60.0, 30.0), *anchor)?;
+            anchors.push(button);
+        }
+        anchors.layout(Point::new(offset.x, offset.y + 60.0));
 
-        let mut object_ui = Rc::new(
-            AssetTypeUi::build_sub_ui(Some(&SpriteType::Object), op, ctx, assets.clone()).unwrap(),
-        );
-        let object: SBAT = SimpleButton::new33(
-            ctx,
-            assets.clone(),
-            "Object",
-            4,
-            op,
-            Box::new(|ui: &mut SubUiContainer| {
-                ui.sub_ui = ui.object_ui.clone();
-            }),
-        );
+        let button = |ctx: &mut Context, text: &str, action: UiAction| {
+            Button::new(ctx, text, &assets.font, BUTTON_SIZE, action)
+        };
+        let narrow = |ctx: &mut Context, text: &str, action: UiAction| {
+            Button::new(ctx, text, &assets.font, KIND_SIZE, action)
+        };
+        let mut actions = Container::vertical(SPACING)
+            .with(
+                Container::horizontal(SPACING)
+                    .with(button(ctx, "Previous", UiAction::Previous)?)
+                    .with(button(ctx, "SAVE!", UiAction::Save)?)
+                    .with(button(ctx, "Next", UiAction::Next)?),
+            )
+            .with(
+                Container::horizontal(SPACING)
+                    .with(narrow(ctx, "Revert", UiAction::Revert)?)
+                    .with(narrow(ctx, "Apply sel.", UiAction::ApplyToSelection)?)
+                    .with(narrow(ctx, "Anims", UiAction::Animations)?)
+                    .with(narrow(ctx, "Tags", UiAction::Tags)?),
+            );
+        actions.layout(Point::new(1400.0, 875.0));
+
+        let mut sub_offset = offset.clone();
+        sub_offset.y += 300.0;
+
+        let squares = match markers {
+            Some(SpriteType::Ground { ref square }) => square.clone(),
+            _ => vec![],
+        };
+        let mask = match markers {
+            Some(SpriteType::GroundMask { mask }) => Some(mask),
+            _ => None,
+        };
+        let horizontal = match markers {
+            Some(SpriteType::Platform { ref horizontal }) => horizontal.clone(),
+            _ => vec![],
+        };
 
-        let mut platform_ui = Rc::new(
-            AssetTypeUi::build_sub_ui(Some(&SpriteType::empty_platform()), op, ctx, assets.clone())
-                .unwrap(),
-        );
-        let platform: SBAT = SimpleButton::new33(
-            ctx,
-            assets.clone(),
-            "Platform",
-            5,
-            op,
-            Box::new(|ui: &mut SubUiContainer| {
-                ui.sub_ui = ui.platform_ui.clone();
+        let mut sub_uis: HashMap<SpriteKind, Box<UiState>> = HashMap::new();
+        sub_uis.insert(
+            SpriteKind::Object,
+            Box::new(NoSubUi {
+                state: Some(SpriteType::Object),
             }),
         );
-
-        let mut ground_ui = Rc::new(
-            AssetTypeUi::build_sub_ui(Some(&SpriteType::empty_platform()), op, ctx, assets.clone())
-                .unwrap(),
+        sub_uis.insert(
+            SpriteKind::Platform,
+            Box::new(PlatformUi::new(ctx, assets.clone(), sub_offset, horizontal)?),
         );
-        let ground: SBAT = SimpleButton::new33(
-            ctx,
-            assets.clone(),
-            "Ground",
-            6,
-            op,
-            Box::new(|ui: &mut SubUiContainer| {
-                ui.sub_ui = ui.ground_ui.clone();
-            }),
+        sub_uis.insert(
+            SpriteKind::Ground,
+            Box::new(GroundUi::new(ctx, assets.clone(), sub_offset, squares, mask)?),
         );
-
-        let save: SBAT = SimpleButton::new33(
-            ctx,
-            assets.clone(),
-            "SAVE!",
-            5,
-            &Point::new(1450.0, 850.0),
-            Box::new(|x| ()),
+        sub_uis.insert(
+            SpriteKind::NineSlice,
+            Box::new(NineSliceUi::new(ctx, assets.clone(), sub_offset, data, image)?),
         );
 
-        let final_ui = Rc::new(ui);
-        let state = (*(*final_ui).borrow_mut()).return_state();
-
-        let mut selected = None;
-
-        match state {
-            Some(SpriteType::Ground { .. }) => {
-                ground_ui = final_ui.clone();
-                selected = Some(ground.rect.clone());
-            }
-            Some(SpriteType::Platform { .. }) => {
-                platform_ui = final_ui.clone();
-                selected = Some(platform.rect.clone());
-            }
-            Some(SpriteType::Object) => {
-                object_ui = final_ui.clone();
-                selected = Some(object.rect.clone());
-            }
-            _ => (),
-        };
-
         Ok(AssetTypeUi {
-            assets,
+            assets: assets.clone(),
             offset,
             state: data.map(|d| d.clone()),
-            hovered: None,
-            selected,
-            sub_ui_container: SubUiContainer {
-                sub_ui: final_ui,
-                object_ui: object_ui,
-                platform_ui: platform_ui,
-                ground_ui: ground_ui,
-            },
-            buttons: AssetUiButtons {
-                object,
-                platform,
-                ground,
-                save,
-            },
+            kind,
+            kinds,
+            anchors,
+            actions,
+            sub_uis,
         })
     }
 
-    fn build_sub_ui(
-        data: Option<&SpriteType>,
-        offset: &Point,
-        ctx: &mut Context,
-        assets: Rc<Assets>,
-    ) -> GameResult<RefCell<Box<UiState>>> {
-        let result: Box<UiState> = match data.map(|d| d.clone()) {
-            Some(markers) => {
-                let mut sub_ui_offset = offset.clone();
-                sub_ui_offset.y += 300.0;
-
-                let ui: Box<UiState> = match markers {
-                    SpriteType::Ground { square: sqr } => {
-                        let ground = GroundUi::new(ctx, assets, sub_ui_offset, sqr)?;
-                        Box::new(ground)
-                    }
-                    SpriteType::Platform { horizontal: hor } => {
-                        let platform = PlatformUi::new(ctx, assets, sub_ui_offset, hor)?;
-                        Box::new(platform)
-                    }
-                    SpriteType::Object => Box::new(NoSubUi {
-                        state: Some(SpriteType::Object),
-                    }),
-                };
-                ui
-            }
-            None => Box::new(NoSubUi { state: None }),
-        };
-        Ok(RefCell::new(result))
+    fn sub_ui(&self) -> Option<&Box<UiState>> {
+        self.kind.as_ref().and_then(|k| self.sub_uis.get(k))
+    }
+
+    pub fn pivot(&self) -> Option<geom::Point> {
+        self.state.as_ref().map(|s| s.pivot.clone())
+    }
+
+    pub fn set_pivot(&mut self, pivot: geom::Point) {
+        if let Some(ref mut state) = self.state {
+            state.pivot = pivot;
+        }
+    }
+
+    /// Nine-slice borders being edited, if the sprite is marked as one.
+    pub fn insets(&self) -> Option<Insets> {
+        self.return_state().and_then(|s| s.insets())
+    }
+
+    pub fn set_insets(&mut self, insets: Insets) {
+        if let Some(ui) = self.sub_uis.get_mut(&SpriteKind::NineSlice) {
+            ui.set_state(SpriteType::NineSlice { insets });
+        }
+    }
+
+    /// Keeps the sprite's tags in step with edits made in the tag editor.
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        if let Some(ref mut state) = self.state {
+            state.tags = tags;
+        }
     }
 
     pub fn full_state(&self) -> Option<SpriteData> {
@@ -301,179 +277,243 @@ impl AssetTypeUi {
 
 impl UiState for AssetTypeUi {
     fn draw(&self, ctx: &mut Context) {
-        self.buttons.borrow().object.draw(ctx);
-        self.buttons.borrow().platform.draw(ctx);
-        self.buttons.borrow().ground.draw(ctx);
-        self.buttons.borrow().save.draw(ctx);
+        self.kinds.draw(ctx).unwrap();
+        self.anchors.draw(ctx).unwrap();
+        self.actions.draw(ctx).unwrap();
 
-        if let Some(h) = self.hovered {
-            draw_rect_with_outline(ctx, Color::new(0.8, 0.0, 0.0, 1.0), &h).unwrap();
-        };
+        if let Some(sub_ui) = self.sub_ui() {
+            sub_ui.draw(ctx);
+        }
+    }
 
-        if let Some(sel) = self.selected {
-            draw_rect_with_outline(ctx, Color::new(0.0, 0.8, 0.2, 1.0), &sel).unwrap();
-        };
+    fn interact(&mut self, ctx: &mut Context, point: &Point) -> GameResult<Option<UiAction>> {
+        if let Some(kind) = self.kinds.click(point) {
+            self.kind = Some(kind);
+            self.kinds.sync(&|k| *k == kind);
+            return Ok(None);
+        }
 
-        (*self.sub_ui_container.sub_ui).borrow().draw(ctx);
-    }
+        if let Some(anchor) = self.anchors.click(point) {
+            self.set_pivot(anchor.pivot());
+            return Ok(None);
+        }
 
-    fn interact(&mut self, ctx: &mut Context, point: &Point) -> GameResult<()> {
-        let buttons = &mut self.buttons;
-        let mut ui = &mut self.sub_ui_container;
+        if let Some(action) = self.actions.click(point) {
+            return Ok(Some(action));
+        }
 
-        if buttons.ground.interact(point, ui) {
-            self.selected = Some(buttons.ground.rect.clone());
-        } else if buttons.platform.interact(point, ui) {
-            self.selected = Some(buttons.platform.rect.clone());
-        } else if buttons.object.interact(point, ui) {
-            self.selected = Some(buttons.object.rect.clone());
-        } else if buttons.save.interact(point, ui) {
-            return Err(GameError::from(String::from("Save now")));
-        } else {
-            (*ui.sub_ui).borrow_mut().interact(ctx, point)?
-        };
-        Ok(())
+        match self.kind {
+            Some(kind) => self.sub_uis.get_mut(&kind).unwrap().interact(ctx, point),
+            None => Ok(None),
+        }
     }
 
     fn hover(&mut self, point: &Point) -> Option<Rect> {
-        let ui = &mut self.sub_ui_container;
-        let buttons = self.buttons.borrow();
-        let buttons_vec = vec![
-            &buttons.object,
-            &buttons.platform,
-            &buttons.ground,
-            &buttons.save,
-        ];
-
-        let rect: Option<Rect> = buttons_vec
-            .iter()
-            .find(|b| b.hover(point))
-            .map(|b| b.rect.clone());
-        self.hovered = rect;
-        if let None = rect {
-            (*ui.sub_ui).borrow_mut().hover(point);
+        let kinds = self.kinds.hover(point);
+        let anchors = self.anchors.hover(point);
+        let actions = self.actions.hover(point);
+        let sub_ui = match self.kind {
+            Some(kind) => self.sub_uis.get_mut(&kind).unwrap().hover(point),
+            None => None,
         };
-        rect
+        kinds.or(anchors).or(actions).or(sub_ui)
     }
 
     fn return_state(&self) -> Option<SpriteType> {
-        (*self.sub_ui_container.sub_ui).borrow().return_state()
+        self.sub_ui().and_then(|ui| ui.return_state())
     }
 }
 
+/// How `GroundUi` marks the tile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GroundMode {
+    Squares,
+    Mask(MaskMode),
+}
+
+/// Neighbour buttons row by row with the rotation of their arrow, the
+/// tile itself in the middle.
+const NEIGHBOUR_ICONS: [(u8, f32); 9] = [
+    (mask::NW, -0.785398),
+    (mask::N, 0.0),
+    (mask::NE, 0.785398),
+    (mask::W, -1.570796),
+    (0, 0.0),
+    (mask::E, 1.570796),
+    (mask::SW, -2.356194),
+    (mask::S, 3.141593),
+    (mask::SE, 2.356194),
+];
+
+/// Marks ground with squares, or with a neighbour mask picked by clicking
+/// the neighbours around the tile that continue it.
 pub struct GroundUi {
+    assets: Rc<Assets>,
     offset: Point,
-    hovered: Option<Rect>,
     state: Vec<Square>,
-    buttons: Vec<SBGU>,
+    /// Set while the tile is marked with a mask instead of squares.
+    mask: Option<GroundMask>,
+    modes: Dropdown<GroundMode>,
+    grid: Container<Square>,
+    neighbours: Container<u8>,
+    /// Why the last mode switch was refused.
+    notice: Option<Text>,
 }
 
-type SBGU = SimpleButton<Vec<Square>>;
 impl GroundUi {
     pub fn new(
         ctx: &mut Context,
         assets: Rc<Assets>,
         offset: Point,
         state: Vec<Square>,
+        mask: Option<GroundMask>,
     ) -> GameResult<GroundUi> {
-        let op = &offset;
-
-        let buttons: Vec<SBGU> = square_icons
-            .iter()
-            .map(|si| {
-                let si = si.clone();
-                SimpleButton::new55(
-                    ctx,
-                    assets.clone(),
-                    si.icon,
-                    si.ix,
-                    si.rotation,
-                    &offset,
-                    Box::new(move |state: &mut Vec<Square>| {
-                        if !state.contains(&si.s) {
-                            distinct_vec_add(state, si.s.clone());
-                        } else {
-                            state.retain(|i| *i != si.s.clone());
-                        };
-                    }),
-                )
-            })
-            .collect();
+        let options = vec![
+            ("Squares", GroundMode::Squares),
+            ("4-bit", GroundMode::Mask(MaskMode::Sides)),
+            ("8-bit", GroundMode::Mask(MaskMode::Blob)),
+        ];
+        let mut modes = Dropdown::new(ctx, &assets.font, (BUTTON_SIZE.0, 30.0), options, 0)?;
+        modes.layout(Point::new(offset.x, offset.y - 200.0));
+
+        let mut grid = Container::grid(5, SPACING);
+
+        for position in 1..26 {
+            match square_icons.iter().find(|si| si.ix == position) {
+                Some(si) => {
+                    let icon = Button::new(ctx, si.icon, &assets.awesome, ICON_SIZE, si.s.clone())?;
+                    let button = icon.rotated(si.rotation);
+                    grid.push(ToggleButton::new(button, state.contains(&si.s)));
+                }
+                None => grid.push(Spacer::new(ICON_SIZE.0, ICON_SIZE.1)),
+            }
+        }
+        grid.layout(offset);
 
-        Ok(GroundUi {
+        let mut neighbours = Container::grid(3, SPACING);
+        for &(bit, rotation) in NEIGHBOUR_ICONS.iter() {
+            let icon = if bit == 0 { "\u{f111}" } else { "\u{f106}" };
+            let button = Button::new(ctx, icon, &assets.awesome, ICON_SIZE, bit)?;
+            neighbours.push(ToggleButton::new(button.rotated(rotation), false));
+        }
+        neighbours.layout(offset);
+
+        let mut ui = GroundUi {
+            assets,
             offset,
-            buttons,
-            hovered: None,
             state,
-        })
+            mask,
+            modes,
+            grid,
+            neighbours,
+            notice: None,
+        };
+        ui.sync();
+        Ok(ui)
     }
 
-    fn draw_selected(&self, ctx: &mut Context) {
-        let color = Color::new(0.0, 0.8, 0.2, 1.0);
-
-        let all_ix = square_icons
-            .iter()
-            .enumerate()
-            .map(|(ix, si)| (si.s.clone(), ix))
-            .collect::<HashMap<_, _>>();
+    fn mode(&self) -> GroundMode {
+        self.mask
+            .map(|m| GroundMode::Mask(m.mode))
+            .unwrap_or(GroundMode::Squares)
+    }
 
-        for s in self.state.iter() {
-            let ix = all_ix.get(s).unwrap();
-            let rect = self.buttons[*ix].rect.clone();
-            draw_rect_with_outline(ctx, color.clone(), &rect).unwrap();
+    /// Converts the marking. A mask squares can't express stays a mask.
+    fn set_mode(&mut self, mode: GroundMode) -> Result<(), &'static str> {
+        match (self.mask, mode) {
+            (Some(mask), GroundMode::Squares) => match mask.squares() {
+                Some(squares) => {
+                    self.state = squares;
+                    self.mask = None;
+                }
+                None => return Err("Squares can't express this mask"),
+            },
+            (Some(mask), GroundMode::Mask(m)) => self.mask = Some(GroundMask::new(m, mask.blob())),
+            (None, GroundMode::Mask(m)) => {
+                // Without squares start from the middle tile, ground on every side.
+                let mask = GroundMask::from_squares(&self.state, m)
+                    .unwrap_or_else(|| GroundMask::new(m, !0));
+                self.mask = Some(mask);
+            }
+            (None, GroundMode::Squares) => (),
         }
+        Ok(())
+    }
+
+    fn sync(&mut self) {
+        let mode = self.mode();
+        self.modes.sync(&|m| *m == mode);
+        let state = &self.state;
+        self.grid.sync(&|s| state.contains(s));
+        let mask = self.mask;
+        self.neighbours
+            .sync(&|&bit| bit == 0 || mask.map(|m| m.has(bit)).unwrap_or(false));
     }
 }
 
 impl UiState for GroundUi {
     fn draw(&self, ctx: &mut Context) {
-        for b in self.buttons.iter() {
-            b.draw(ctx);
+        if self.mask.is_some() {
+            self.neighbours.draw(ctx).unwrap();
+        } else {
+            self.grid.draw(ctx).unwrap();
         }
-
-        self.draw_selected(ctx);
-
-        if let Some(h) = self.hovered {
-            draw_rect_with_outline(ctx, Color::new(0.8, 0.0, 0.0, 1.0), &h).unwrap();
-        };
+        if let Some(ref notice) = self.notice {
+            let dest = Point::new(self.offset.x, self.offset.y - 160.0);
+            graphics::draw(ctx, notice, dest, 0.0).unwrap();
+        }
+        // Unfolded modes cover the marks.
+        self.modes.draw(ctx).unwrap();
     }
 
-    fn interact(&mut self, ctx: &mut Context, point: &Point) -> GameResult<()> {
-        let mut ns = self.state.clone();
-
-        'find: for mut b in self.buttons.iter_mut() {
-            if b.interact(point, &mut ns) {
-                break 'find;
+    fn interact(&mut self, ctx: &mut Context, point: &Point) -> GameResult<Option<UiAction>> {
+        let was_open = self.modes.is_open();
+        if let Some(mode) = self.modes.click(point) {
+            self.notice = match self.set_mode(mode) {
+                Ok(()) => None,
+                Err(reason) => Some(Text::new(ctx, reason, &self.assets.font)?),
+            };
+        } else if was_open {
+            // The click only folded the modes.
+        } else if let Some(mask) = self.mask {
+            // The middle button is the tile itself, bit 0 changes nothing.
+            if let Some(bit) = self.neighbours.click(point) {
+                self.mask = Some(mask.toggled(bit));
             }
+        } else if let Some(square) = self.grid.click(point) {
+            toggle_in(&mut self.state, square);
         }
-        self.state = ns;
-        Ok(())
+        self.sync();
+        Ok(None)
     }
 
     fn hover(&mut self, point: &Point) -> Option<Rect> {
-        let rect = self.buttons
-            .iter()
-            .find(|b| b.hover(point))
-            .map(|b| b.rect.clone());
-        self.hovered = rect;
-        rect
+        let modes = self.modes.hover(point);
+        if self.modes.is_open() {
+            return modes;
+        }
+        let marks = if self.mask.is_some() {
+            self.neighbours.hover(point)
+        } else {
+            self.grid.hover(point)
+        };
+        modes.or(marks)
     }
 
     fn return_state(&self) -> Option<SpriteType> {
-        Some(SpriteType::Ground {
-            square: self.state.clone(),
-        })
+        match self.mask {
+            Some(mask) => Some(SpriteType::GroundMask { mask }),
+            None => Some(SpriteType::Ground {
+                square: self.state.clone(),
+            }),
+        }
     }
 }
 
-type SBPI = SimpleButton<Vec<Horizontal>>;
 pub struct PlatformUi {
     offset: Point,
-    hovered: Option<Rect>,
     state: Vec<Horizontal>,
-    left: SBPI,
-    center: SBPI,
-    right: SBPI,
+    row: Container<Horizontal>,
 }
 
 impl PlatformUi {
@@ -483,108 +523,34 @@ impl PlatformUi {
         offset: Point,
         state: Vec<Horizontal>,
     ) -> GameResult<PlatformUi> {
-        let clj = |mut state: &mut Vec<Horizontal>, s: Horizontal| {
-            if !state.contains(&s) {
-                distinct_vec_add(&mut state, s);
-            } else {
-                state.retain(|i| *i != s);
-            };
-        };
-
-        let clj = Rc::new(clj);
-        let clj2 = clj.clone();
-        let clj3 = clj.clone();
-
-        let left = SimpleButton::new33(
-            ctx,
-            assets.clone(),
-            "Left",
-            4,
-            &offset,
-            Box::new(move |state: &mut Vec<Horizontal>| {
-                clj(state, Horizontal::Left)
-            }),
-        );
-        let center = SimpleButton::new33(
-            ctx,
-            assets.clone(),
-            "Center",
-            5,
-            &offset,
-            Box::new(move |state: &mut Vec<Horizontal>| {
-                clj2(state, Horizontal::Center)
-            }),
-        );
-        let right = SimpleButton::new33(
-            ctx,
-            assets.clone(),
-            "Right",
-            6,
-            &offset,
-            Box::new(move |state: &mut Vec<Horizontal>| {
-                clj3(state, Horizontal::Right)
-            }),
-        );
-
-        Ok(PlatformUi {
-            offset,
-            hovered: None,
-            state,
-            left,
-            center,
-            right,
-        })
-    }
-
-    fn draw_selected(&self, ctx: &mut Context) {
-        let color = Color::new(0.0, 0.8, 0.2, 1.0);
-        let offset = &self.offset;
-
-        if self.state.contains(&Horizontal::Left) {
-            draw_rect_with_outline(ctx, color.clone(), &self.left.rect).unwrap();
-        };
-        if self.state.contains(&Horizontal::Right) {
-            draw_rect_with_outline(ctx, color.clone(), &self.right.rect).unwrap();
-        };
-        if self.state.contains(&Horizontal::Center) {
-            draw_rect_with_outline(ctx, color.clone(), &self.center.rect).unwrap();
-        };
+        let check = |ctx: &mut Context, label, h| Checkbox::new(ctx, label, &assets.font, false, h);
+        let mut row = Container::horizontal(SPACING)
+            .with(check(ctx, "Left", Horizontal::Left)?)
+            .with(check(ctx, "Center", Horizontal::Center)?)
+            .with(check(ctx, "Right", Horizontal::Right)?);
+        row.layout(offset);
+        row.sync(&|h| state.contains(h));
+
+        Ok(PlatformUi { offset, state, row })
     }
 }
 
 impl UiState for PlatformUi {
     fn draw(&self, ctx: &mut Context) {
-        let mut vec = vec![&self.left, &self.center, &self.right];
-        for b in vec.iter() {
-            b.draw(ctx);
-        }
-
-        self.draw_selected(ctx);
-
-        if let Some(h) = self.hovered {
-            draw_rect_with_outline(ctx, Color::new(0.8, 0.0, 0.0, 1.0), &h).unwrap();
-        };
+        self.row.draw(ctx).unwrap();
     }
 
-    fn interact(&mut self, ctx: &mut Context, point: &Point) -> GameResult<()> {
-        let mut state = self.state.clone();
-        let mut vec = vec![&mut self.left, &mut self.center, &mut self.right];
-
-        'find: for ref mut b in vec.iter_mut() {
-            if b.interact(point, &mut state) {
-                break 'find;
-            }
+    fn interact(&mut self, _ctx: &mut Context, point: &Point) -> GameResult<Option<UiAction>> {
+        if let Some(h) = self.row.click(point) {
+            toggle_in(&mut self.state, h);
+            let state = &self.state;
+            self.row.sync(&|h| state.contains(h));
         }
-        self.state = state;
-        Ok(())
+        Ok(None)
     }
 
     fn hover(&mut self, point: &Point) -> Option<Rect> {
-        let mut vec = vec![&mut self.left, &mut self.center, &mut self.right];
-
-        let rect = vec.iter().find(|b| b.hover(point)).map(|b| b.rect.clone());
-        self.hovered = rect;
-        rect
+        self.row.hover(point)
     }
 
     fn return_state(&self) -> Option<SpriteType> {
@@ -601,8 +567,8 @@ pub struct NoSubUi {
 impl UiState for NoSubUi {
     fn draw(&self, _ctx: &mut Context) {}
 
-    fn interact(&mut self, _ctx: &mut Context, _point: &Point) -> GameResult<()> {
-        Ok(())
+    fn interact(&mut self, _ctx: &mut Context, _point: &Point) -> GameResult<Option<UiAction>> {
+        Ok(None)
     }
 
     fn hover(&mut self, _point: &Point) -> Option<Rect> {
@@ -630,12 +596,6 @@ pub fn draw_rect_with_outline(ctx: &mut Context, mut color: Color, rect: &Rect)
     Ok(())
 }
 
-fn distinct_vec_add<T: Hash + Eq + PartialEq>(vec: &mut Vec<T>, value: T) {
-    vec.push(value);
-    let set: HashSet<_> = vec.drain(..).collect();
-    vec.extend(set.into_iter());
-}
-
 fn center(r: &Rect) -> Point {
     Point::new((r.left() + r.right()) / 2.0, (r.top() + r.bottom()) / 2.0)
 }
@@ -0,0 +1,272 @@
+use ggez::graphics::*;
+use ggez::graphics;
+use ggez::{Context, GameResult};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::super::Assets;
+use super::super::marker::*;
+use super::super::sprite::geom;
+use super::widgets::*;
+use super::{UiAction, UiState, SPACING};
+
+/// Room for the stretched preview, width and height.
+const PREVIEW_BOX: (f32, f32) = (370.0, 220.0);
+/// Small sprites aren't blown up further than this in the preview.
+const MAX_ZOOM: f32 = 4.0;
+/// The preview can't grow beyond this many times the frame.
+const MAX_STRETCH: f32 = 8.0;
+
+/// One of the four lines splitting a sprite into nine slices.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Guide {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Guide {
+    pub fn all() -> [Guide; 4] {
+        [Guide::Left, Guide::Right, Guide::Top, Guide::Bottom]
+    }
+
+    /// Left and right guides are vertical lines, they move along x.
+    pub fn is_vertical(&self) -> bool {
+        *self == Guide::Left || *self == Guide::Right
+    }
+
+    /// Screen coordinate of the guide, x or y, with `frame` drawn at `screen`.
+    pub fn position(&self, insets: &Insets, frame: &geom::Rect, screen: &Rect) -> f32 {
+        let k = screen.w / frame.w;
+        let (left, top) = (screen.x - screen.w / 2.0, screen.y - screen.h / 2.0);
+        match *self {
+            Guide::Left => left + insets.left as f32 * k,
+            Guide::Right => left + screen.w - insets.right as f32 * k,
+            Guide::Top => top + insets.top as f32 * k,
+            Guide::Bottom => top + screen.h - insets.bottom as f32 * k,
+        }
+    }
+
+    /// Insets with the guide dragged to `point`, in whole pixels and never
+    /// crossing the opposite guide.
+    pub fn moved(
+        &self,
+        insets: &Insets,
+        frame: &geom::Rect,
+        screen: &Rect,
+        point: &Point,
+    ) -> Insets {
+        let k = screen.w / frame.w;
+        let (left, top) = (screen.x - screen.w / 2.0, screen.y - screen.h / 2.0);
+        let pixels = |v: f32, max: f32| (v / k).round().min(max).max(0.0) as u32;
+
+        let mut moved = *insets;
+        match *self {
+            Guide::Left => moved.left = pixels(point.x - left, frame.w - insets.right as f32),
+            Guide::Right => {
+                moved.right = pixels(left + screen.w - point.x, frame.w - insets.left as f32)
+            }
+            Guide::Top => moved.top = pixels(point.y - top, frame.h - insets.bottom as f32),
+            Guide::Bottom => {
+                moved.bottom = pixels(top + screen.h - point.y, frame.h - insets.top as f32)
+            }
+        }
+        moved
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Resize {
+    Narrower,
+    Wider,
+    Shorter,
+    Taller,
+}
+
+/// Source offset and size, then target offset and size, of the three slices
+/// along one axis.
+fn slices(start: u32, end: u32, source: f32, target: f32) -> [(f32, f32, f32, f32); 3] {
+    let (a, b) = (start as f32, end as f32);
+    [
+        (0.0, a, 0.0, a),
+        (a, (source - a - b).max(0.0), a, (target - a - b).max(0.0)),
+        (source - b, b, target - b, b),
+    ]
+}
+
+/// Nine-slice borders of a UI sprite. The guides are dragged on the
+/// selected sprite, the panel previews the sprite stretched to a size
+/// picked with the resize buttons.
+pub struct NineSliceUi {
+    assets: Rc<Assets>,
+    offset: Point,
+    insets: Insets,
+    image: Option<Rc<Image>>,
+    uv: geom::Rect,
+    frame: geom::Rect,
+    /// Preview size in logical pixels.
+    size: (u32, u32),
+    resize: Container<Resize>,
+    status: RefCell<Option<(String, Text)>>,
+}
+
+impl NineSliceUi {
+    pub fn new(
+        ctx: &mut Context,
+        assets: Rc<Assets>,
+        offset: Point,
+        data: Option<&SpriteData>,
+        image: Option<Rc<Image>>,
+    ) -> GameResult<NineSliceUi> {
+        let insets = data.and_then(|d| d.markers.insets()).unwrap_or_default();
+        let uv = data.map(|d| d.on_screen_frame.clone())
+            .unwrap_or_else(|| geom::Rect::new(0.0, 0.0, 1.0, 1.0));
+        let frame = data.map(|d| d.frame.clone())
+            .unwrap_or_else(|| geom::Rect::new(0.0, 0.0, 1.0, 1.0));
+
+        let mut resize = Container::horizontal(SPACING);
+        for &(label, message) in [
+            ("W-", Resize::Narrower),
+            ("W+", Resize::Wider),
+            ("H-", Resize::Shorter),
+            ("H+", Resize::Taller),
+        ].iter()
+        {
+            resize.push(Button::new(ctx, label, &assets.font, (60.0, 30.0), message)?);
+        }
+        resize.layout(Point::new(offset.x, offset.y - 140.0));
+
+        Ok(NineSliceUi {
+            assets,
+            offset,
+            insets,
+            image,
+            uv,
+            size: ((frame.w * 2.0) as u32, (frame.h * 2.0) as u32),
+            frame,
+            resize,
+            status: RefCell::new(None),
+        })
+    }
+
+    fn resize(&mut self, resize: Resize) {
+        let step = |length: f32| (length / 4.0).round().max(1.0) as u32;
+        let (w, h) = self.size;
+        let (min_w, min_h) = (
+            (self.insets.left + self.insets.right).max(1),
+            (self.insets.top + self.insets.bottom).max(1),
+        );
+        let (max_w, max_h) = (
+            (self.frame.w * MAX_STRETCH) as u32,
+            (self.frame.h * MAX_STRETCH) as u32,
+        );
+
+        self.size = match resize {
+            Resize::Narrower => (w.saturating_sub(step(self.frame.w)).max(min_w), h),
+            Resize::Wider => ((w + step(self.frame.w)).min(max_w), h),
+            Resize::Shorter => (w, h.saturating_sub(step(self.frame.h)).max(min_h)),
+            Resize::Taller => (w, (h + step(self.frame.h)).min(max_h)),
+        };
+    }
+
+    fn preview_center(&self) -> Point {
+        Point::new(self.offset.x, self.offset.y + 30.0)
+    }
+
+    fn draw_status(&self, ctx: &mut Context) -> GameResult<()> {
+        let i = self.insets;
+        let status = format!(
+            "L {} R {} T {} B {}   {}x{}",
+            i.left, i.right, i.top, i.bottom, self.size.0, self.size.1
+        );
+
+        let mut cache = self.status.borrow_mut();
+        if cache.as_ref().map(|c| c.0 != status).unwrap_or(true) {
+            let text = Text::new(ctx, &status, &self.assets.font)?;
+            *cache = Some((status, text));
+        }
+        if let Some((_, ref text)) = *cache {
+            let dest = Point::new(self.offset.x, self.offset.y - 105.0);
+            graphics::draw(ctx, text, dest, 0.0)?;
+        }
+        Ok(())
+    }
+
+    /// Draws every slice on its own, corners unscaled and the rest stretched.
+    fn draw_preview(&self, ctx: &mut Context, image: &Image) -> GameResult<()> {
+        let (fw, fh) = (self.frame.w, self.frame.h);
+        let (w, h) = (self.size.0 as f32, self.size.1 as f32);
+        let k = (PREVIEW_BOX.0 / w).min(PREVIEW_BOX.1 / h).min(MAX_ZOOM);
+
+        // Atlas pixels per logical pixel, more than one for @2x sheets.
+        let ax = self.uv.w * image.width() as f32 / fw;
+        let ay = self.uv.h * image.height() as f32 / fh;
+
+        let center = self.preview_center();
+        let (left, top) = (center.x - w * k / 2.0, center.y - h * k / 2.0);
+
+        let columns = slices(self.insets.left, self.insets.right, fw, w);
+        let rows = slices(self.insets.top, self.insets.bottom, fh, h);
+        for &(sx, sw, tx, tw) in columns.iter() {
+            for &(sy, sh, ty, th) in rows.iter() {
+                if sw <= 0.0 || sh <= 0.0 || tw <= 0.0 || th <= 0.0 {
+                    continue;
+                }
+                let src = Rect::new(
+                    self.uv.x + self.uv.w * sx / fw,
+                    self.uv.y + self.uv.h * sy / fh,
+                    self.uv.w * sw / fw,
+                    self.uv.h * sh / fh,
+                );
+                let param = DrawParam {
+                    src,
+                    dest: Point::new(left + (tx + tw / 2.0) * k, top + (ty + th / 2.0) * k),
+                    scale: Point::new(tw * k / (sw * ax), th * k / (sh * ay)),
+                    offset: Point::zero(),
+                    ..Default::default()
+                };
+                graphics::draw_ex(ctx, image, param)?;
+            }
+        }
+
+        graphics::rectangle(
+            ctx,
+            DrawMode::Line,
+            Rect::new(center.x, center.y, w * k, h * k),
+        )
+    }
+}
+
+impl UiState for NineSliceUi {
+    fn draw(&self, ctx: &mut Context) {
+        self.resize.draw(ctx).unwrap();
+        self.draw_status(ctx).unwrap();
+        if let Some(ref image) = self.image {
+            self.draw_preview(ctx, image).unwrap();
+        }
+    }
+
+    fn interact(&mut self, _ctx: &mut Context, point: &Point) -> GameResult<Option<UiAction>> {
+        if let Some(resize) = self.resize.click(point) {
+            self.resize(resize);
+        }
+        Ok(None)
+    }
+
+    fn hover(&mut self, point: &Point) -> Option<Rect> {
+        self.resize.hover(point)
+    }
+
+    fn return_state(&self) -> Option<SpriteType> {
+        Some(SpriteType::NineSlice {
+            insets: self.insets,
+        })
+    }
+
+    fn set_state(&mut self, state: SpriteType) {
+        if let Some(insets) = state.insets() {
+            self.insets = insets;
+        }
+    }
+}
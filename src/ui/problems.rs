@@ -0,0 +1,114 @@
+use ggez::graphics::*;
+use ggez::graphics;
+use ggez::{Context, GameResult};
+use std::rc::Rc;
+
+use super::super::Assets;
+use super::super::lint::{Problem, Severity};
+use super::{draw_rect_with_outline, point_within};
+
+const ROW_HEIGHT: f32 = 24.0;
+const MAX_ROWS: usize = 6;
+
+struct ProblemRow {
+    rect: Rect,
+    text: Text,
+    severity: Severity,
+    sprite: Option<usize>,
+}
+
+/// List of lint problems, clicking a row jumps to the offending sprite.
+pub struct ProblemsUi {
+    assets: Rc<Assets>,
+    offset: Point,
+    width: f32,
+    rows: Vec<ProblemRow>,
+    summary: Option<Text>,
+    hovered: Option<Rect>,
+}
+
+impl ProblemsUi {
+    pub fn new(assets: Rc<Assets>, offset: Point, width: f32) -> ProblemsUi {
+        ProblemsUi {
+            assets,
+            offset,
+            width,
+            rows: vec![],
+            summary: None,
+            hovered: None,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut Context, problems: &[Problem]) -> GameResult<()> {
+        self.rows.clear();
+        self.hovered = None;
+        self.summary = if problems.len() > MAX_ROWS {
+            let more = format!("... and {} more", problems.len() - MAX_ROWS);
+            Some(Text::new(ctx, &more, &self.assets.font)?)
+        } else {
+            None
+        };
+
+        for (ix, p) in problems.iter().take(MAX_ROWS).enumerate() {
+            let rect = Rect::new(
+                self.offset.x,
+                self.offset.y + ix as f32 * ROW_HEIGHT,
+                self.width,
+                ROW_HEIGHT,
+            );
+            let label = format!("{}: {}", p.name, p.message);
+            self.rows.push(ProblemRow {
+                rect,
+                text: Text::new(ctx, &label, &self.assets.font)?,
+                severity: p.severity,
+                sprite: p.sprite,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn hover(&mut self, point: &Point) -> Option<Rect> {
+        self.hovered = self.rows
+            .iter()
+            .find(|r| point_within(point, &r.rect))
+            .map(|r| r.rect.clone());
+        self.hovered
+    }
+
+    /// Sprite of the clicked row.
+    pub fn interact(&self, point: &Point) -> Option<usize> {
+        self.rows
+            .iter()
+            .find(|r| point_within(point, &r.rect))
+            .and_then(|r| r.sprite)
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        for row in self.rows.iter() {
+            let color = match row.severity {
+                Severity::Error => Color::new(0.9, 0.1, 0.1, 1.0),
+                Severity::Warning => Color::new(0.9, 0.7, 0.1, 1.0),
+            };
+            graphics::set_color(ctx, color)?;
+            let dest = Point::new(
+                row.rect.left() + 4.0 + row.text.width() as f32 / 2.0,
+                row.rect.y,
+            );
+            graphics::draw(ctx, &row.text, dest, 0.0)?;
+        }
+        graphics::set_color(ctx, graphics::WHITE)?;
+
+        if let Some(ref summary) = self.summary {
+            let dest = Point::new(
+                self.offset.x - self.width / 2.0 + 4.0 + summary.width() as f32 / 2.0,
+                self.offset.y + MAX_ROWS as f32 * ROW_HEIGHT,
+            );
+            graphics::draw(ctx, summary, dest, 0.0)?;
+        }
+
+        if let Some(h) = self.hovered {
+            draw_rect_with_outline(ctx, Color::new(0.8, 0.0, 0.0, 1.0), &h)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use ggez::event::Keycode;
+use ggez::graphics;
+use ggez::graphics::*;
+use ggez::{Context, GameResult};
+use std::rc::Rc;
+
+use super::super::marker::*;
+use super::super::Assets;
+use super::widgets::*;
+use super::{point_within, BUTTON_SIZE, SPACING};
+
+/// The panel covers the right column below the filter.
+const PANEL: (f32, f32, f32, f32) = (1400.0, 540.0, 380.0, 920.0);
+const TAG_SIZE: (f32, f32) = (290.0, 30.0);
+const SHOW_SIZE: (f32, f32) = (70.0, 30.0);
+const TAG_ROWS: usize = 20;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagsMsg {
+    /// Adds the tag to every target sprite.
+    Add(String),
+    /// Takes the tag off the targets if all of them have it, adds it otherwise.
+    Toggle(String),
+    /// Shows only the sprites with the tag in the grid.
+    Filter(String),
+    Close,
+}
+
+/// Tags of the sprites being edited, the multi-selection or else the
+/// selected sprite. Sprites can still be picked in the grid while the panel
+/// is open, `Game` applies what the panel asks for.
+pub struct TagsUi {
+    assets: Rc<Assets>,
+    /// Targets and sheet tags the widgets were built for.
+    shown: (Vec<usize>, Vec<String>),
+    title: Container<TagsMsg>,
+    input: TextInput<TagsMsg>,
+    tags: Container<TagsMsg>,
+    actions: Container<TagsMsg>,
+}
+
+impl TagsUi {
+    pub fn new(
+        ctx: &mut Context,
+        assets: Rc<Assets>,
+        marked: &[SpriteData],
+        targets: &[usize],
+    ) -> GameResult<TagsUi> {
+        let top = PANEL.1 - PANEL.3 / 2.0;
+
+        let mut input = TextInput::new(
+            assets.clone(),
+            PANEL.2 - 10.0,
+            "New tag, Enter adds it",
+            Box::new(|value: &str| TagsMsg::Add(String::from(value))),
+        );
+        input.layout(Point::new(PANEL.0, top + 75.0));
+
+        let close = Button::new(ctx, "Close", &assets.font, BUTTON_SIZE, TagsMsg::Close)?;
+        let mut actions = Container::horizontal(SPACING).with(close);
+        actions.layout(Point::new(PANEL.0, top + PANEL.3 - 35.0));
+
+        let mut ui = TagsUi {
+            assets,
+            shown: (vec![], vec![]),
+            title: Container::horizontal(SPACING),
+            input,
+            tags: Container::grid(2, SPACING),
+            actions,
+        };
+        ui.rebuild(ctx, marked, targets)?;
+        Ok(ui)
+    }
+
+    fn rebuild(
+        &mut self,
+        ctx: &mut Context,
+        marked: &[SpriteData],
+        targets: &[usize],
+    ) -> GameResult<()> {
+        let font = &self.assets.font;
+        let top = PANEL.1 - PANEL.3 / 2.0;
+        let tags = all_tags(marked);
+
+        let title = match targets.len() {
+            0 => String::from("Select sprites to tag"),
+            1 => String::from("Tags of 1 sprite"),
+            n => format!("Tags of {} sprites", n),
+        };
+        self.title = Container::horizontal(SPACING).with(Label::new(ctx, &title, font)?);
+        self.title.layout(Point::new(PANEL.0, top + 30.0));
+
+        self.tags = Container::grid(2, SPACING);
+        for tag in tags.iter().take(TAG_ROWS) {
+            let button = Button::new(ctx, tag, font, TAG_SIZE, TagsMsg::Toggle(tag.clone()))?;
+            self.tags.push(ToggleButton::new(button, false));
+            let msg = TagsMsg::Filter(tag.clone());
+            self.tags.push(Button::new(ctx, "Show", font, SHOW_SIZE, msg)?);
+        }
+        let (_, h) = self.tags.size();
+        self.tags.layout(Point::new(PANEL.0, top + 110.0 + h / 2.0));
+
+        self.shown = (targets.to_vec(), tags);
+        Ok(())
+    }
+
+    /// Follows the selection in the grid and the tags of the sheet, needs
+    /// the context for text.
+    pub fn update(
+        &mut self,
+        ctx: &mut Context,
+        marked: &[SpriteData],
+        targets: &[usize],
+    ) -> GameResult<()> {
+        if self.shown.0 != targets || self.shown.1 != all_tags(marked) {
+            self.rebuild(ctx, marked, targets)?;
+        }
+
+        let all_have = |tag: &str| {
+            !targets.is_empty() && targets.iter().all(|&ix| marked[ix].has_tag(tag))
+        };
+        self.tags.sync(&|msg: &TagsMsg| match *msg {
+            TagsMsg::Toggle(ref tag) => all_have(tag),
+            _ => false,
+        });
+        Ok(())
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        point_within(point, &Rect::new(PANEL.0, PANEL.1, PANEL.2, PANEL.3))
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.input.is_focused()
+    }
+
+    pub fn hover(&mut self, point: &Point) -> Option<Rect> {
+        let widgets = [
+            self.input.hover(point),
+            self.tags.hover(point),
+            self.actions.hover(point),
+        ];
+        widgets.iter().fold(None, |found, w| found.or(*w))
+    }
+
+    pub fn interact(&mut self, point: &Point) -> Option<TagsMsg> {
+        self.input
+            .click(point)
+            .or_else(|| self.tags.click(point))
+            .or_else(|| self.actions.click(point))
+    }
+
+    pub fn key(&mut self, keycode: Keycode, shift: bool) -> Option<TagsMsg> {
+        // Escape unfocuses the input first, a second one closes the panel.
+        if keycode == Keycode::Escape && !self.input.is_focused() {
+            return Some(TagsMsg::Close);
+        }
+        match self.input.key(keycode, shift) {
+            Some(TagsMsg::Add(tag)) => {
+                self.input.set_value("");
+                Some(TagsMsg::Add(tag))
+            }
+            other => other,
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let panel = Rect::new(PANEL.0, PANEL.1, PANEL.2, PANEL.3);
+        graphics::set_color(ctx, Color::new(0.1, 0.1, 0.1, 1.0))?;
+        graphics::rectangle(ctx, DrawMode::Fill, panel)?;
+        graphics::set_color(ctx, graphics::WHITE)?;
+        graphics::rectangle(ctx, DrawMode::Line, panel)?;
+
+        self.title.draw(ctx)?;
+        self.input.draw(ctx)?;
+        self.tags.draw(ctx)?;
+        self.actions.draw(ctx)
+    }
+}
@@ -0,0 +1,866 @@
+use ggez::graphics::*;
+use ggez::graphics;
+use ggez::event::Keycode;
+use ggez::{Context, GameResult};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::super::Assets;
+use super::{center, draw_rect_with_outline, point_within};
+
+const PADDING: f32 = 10.0;
+
+fn hover_color() -> Color {
+    Color::new(0.8, 0.0, 0.0, 1.0)
+}
+
+fn selected_color() -> Color {
+    Color::new(0.0, 0.8, 0.2, 1.0)
+}
+
+/// Retained-mode UI element. Widgets are laid out once by their container
+/// and report clicks as messages of type `M` for the owning panel to handle.
+pub trait Widget<M> {
+    /// Preferred size, width and height.
+    fn size(&self) -> (f32, f32);
+    /// Places the widget with its center at `center`.
+    fn layout(&mut self, center: Point);
+    fn rect(&self) -> Rect;
+    fn draw(&self, ctx: &mut Context) -> GameResult<()>;
+
+    fn hover(&mut self, _point: &Point) -> Option<Rect> {
+        None
+    }
+
+    fn click(&mut self, _point: &Point) -> Option<M> {
+        None
+    }
+
+    fn key(&mut self, _keycode: Keycode, _shift: bool) -> Option<M> {
+        None
+    }
+
+    /// Updates toggle states from the panel's state.
+    fn sync(&mut self, _is_on: &Fn(&M) -> bool) {}
+}
+
+fn sized_rect(size: (f32, f32)) -> Rect {
+    Rect::new(0.0, 0.0, size.0, size.1)
+}
+
+fn draw_text_at(ctx: &mut Context, text: &Text, left: f32, y: f32) -> GameResult<()> {
+    let dest = Point::new(left + text.width() as f32 / 2.0, y);
+    graphics::draw(ctx, text, dest, 0.0)
+}
+
+/// Empty cell, keeps grids aligned.
+pub struct Spacer {
+    rect: Rect,
+}
+
+impl Spacer {
+    pub fn new(w: f32, h: f32) -> Spacer {
+        Spacer {
+            rect: sized_rect((w, h)),
+        }
+    }
+}
+
+impl<M> Widget<M> for Spacer {
+    fn size(&self) -> (f32, f32) {
+        (self.rect.w, self.rect.h)
+    }
+
+    fn layout(&mut self, center: Point) {
+        self.rect.x = center.x;
+        self.rect.y = center.y;
+    }
+
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn draw(&self, _ctx: &mut Context) -> GameResult<()> {
+        Ok(())
+    }
+}
+
+pub struct Label {
+    text: Text,
+    rect: Rect,
+}
+
+impl Label {
+    pub fn new(ctx: &mut Context, text: &str, font: &Font) -> GameResult<Label> {
+        let text = Text::new(ctx, text, font)?;
+        let rect = sized_rect((text.width() as f32 + PADDING, text.height() as f32 + PADDING));
+        Ok(Label { text, rect })
+    }
+}
+
+impl<M> Widget<M> for Label {
+    fn size(&self) -> (f32, f32) {
+        (self.rect.w, self.rect.h)
+    }
+
+    fn layout(&mut self, center: Point) {
+        self.rect.x = center.x;
+        self.rect.y = center.y;
+    }
+
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        graphics::draw(ctx, &self.text, center(&self.rect), 0.0)
+    }
+}
+
+pub struct Button<M> {
+    text: Text,
+    rect: Rect,
+    rotation: f32,
+    message: M,
+    hovered: bool,
+}
+
+impl<M: Clone> Button<M> {
+    pub fn new(
+        ctx: &mut Context,
+        text: &str,
+        font: &Font,
+        size: (f32, f32),
+        message: M,
+    ) -> GameResult<Button<M>> {
+        Ok(Button {
+            text: Text::new(ctx, text, font)?,
+            rect: sized_rect(size),
+            rotation: 0.0,
+            message,
+            hovered: false,
+        })
+    }
+
+    /// Rotates the label, used to point arrow icons.
+    pub fn rotated(mut self, rotation: f32) -> Button<M> {
+        self.rotation = rotation;
+        self
+    }
+
+    fn draw_button(&self, ctx: &mut Context) -> GameResult<()> {
+        graphics::rectangle(ctx, DrawMode::Line, self.rect)?;
+        graphics::draw(ctx, &self.text, center(&self.rect), self.rotation)?;
+        if self.hovered {
+            draw_rect_with_outline(ctx, hover_color(), &self.rect)?;
+        }
+        Ok(())
+    }
+}
+
+impl<M: Clone> Widget<M> for Button<M> {
+    fn size(&self) -> (f32, f32) {
+        (self.rect.w, self.rect.h)
+    }
+
+    fn layout(&mut self, center: Point) {
+        self.rect.x = center.x;
+        self.rect.y = center.y;
+    }
+
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        self.draw_button(ctx)
+    }
+
+    fn hover(&mut self, point: &Point) -> Option<Rect> {
+        self.hovered = point_within(point, &self.rect);
+        if self.hovered {
+            Some(self.rect)
+        } else {
+            None
+        }
+    }
+
+    fn click(&mut self, point: &Point) -> Option<M> {
+        if point_within(point, &self.rect) {
+            Some(self.message.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Button that shows whether its message is part of the panel state.
+/// The state itself lives in the panel and is pushed in with `sync`.
+pub struct ToggleButton<M> {
+    button: Button<M>,
+    on: bool,
+}
+
+impl<M: Clone> ToggleButton<M> {
+    pub fn new(button: Button<M>, on: bool) -> ToggleButton<M> {
+        ToggleButton { button, on }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+}
+
+impl<M: Clone> Widget<M> for ToggleButton<M> {
+    fn size(&self) -> (f32, f32) {
+        self.button.size()
+    }
+
+    fn layout(&mut self, center: Point) {
+        self.button.layout(center)
+    }
+
+    fn rect(&self) -> Rect {
+        self.button.rect
+    }
+
+    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        self.button.draw_button(ctx)?;
+        if self.on {
+            draw_rect_with_outline(ctx, selected_color(), &self.button.rect)?;
+        }
+        Ok(())
+    }
+
+    fn hover(&mut self, point: &Point) -> Option<Rect> {
+        self.button.hover(point)
+    }
+
+    fn click(&mut self, point: &Point) -> Option<M> {
+        self.button.click(point)
+    }
+
+    fn sync(&mut self, is_on: &Fn(&M) -> bool) {
+        self.on = is_on(&self.button.message);
+    }
+}
+
+/// Checked state of a `Checkbox`, kept apart from its label.
+struct Check<M> {
+    rect: Rect,
+    checked: bool,
+    message: M,
+}
+
+impl<M: Clone> Check<M> {
+    fn click(&mut self, point: &Point) -> Option<M> {
+        if point_within(point, &self.rect) {
+            self.checked = !self.checked;
+            Some(self.message.clone())
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Checkbox<M> {
+    label: Text,
+    check: Check<M>,
+    hovered: bool,
+}
+
+impl<M: Clone> Checkbox<M> {
+    pub fn new(
+        ctx: &mut Context,
+        label: &str,
+        font: &Font,
+        checked: bool,
+        message: M,
+    ) -> GameResult<Checkbox<M>> {
+        let label = Text::new(ctx, label, font)?;
+        let h = label.height() as f32 + PADDING;
+        let rect = sized_rect((h + label.width() as f32 + PADDING, h));
+        Ok(Checkbox {
+            label,
+            check: Check {
+                rect,
+                checked,
+                message,
+            },
+            hovered: false,
+        })
+    }
+
+    pub fn is_checked(&self) -> bool {
+        self.check.checked
+    }
+
+    fn box_rect(&self) -> Rect {
+        let rect = self.check.rect;
+        let side = rect.h - PADDING;
+        Rect::new(rect.left() + rect.h / 2.0, rect.y, side, side)
+    }
+}
+
+impl<M: Clone> Widget<M> for Checkbox<M> {
+    fn size(&self) -> (f32, f32) {
+        (self.check.rect.w, self.check.rect.h)
+    }
+
+    fn layout(&mut self, center: Point) {
+        self.check.rect.x = center.x;
+        self.check.rect.y = center.y;
+    }
+
+    fn rect(&self) -> Rect {
+        self.check.rect
+    }
+
+    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let rect = self.check.rect;
+        let mode = if self.check.checked {
+            DrawMode::Fill
+        } else {
+            DrawMode::Line
+        };
+        graphics::rectangle(ctx, mode, self.box_rect())?;
+        draw_text_at(ctx, &self.label, rect.left() + rect.h, rect.y)?;
+        if self.hovered {
+            draw_rect_with_outline(ctx, hover_color(), &rect)?;
+        }
+        Ok(())
+    }
+
+    fn hover(&mut self, point: &Point) -> Option<Rect> {
+        self.hovered = point_within(point, &self.check.rect);
+        if self.hovered {
+            Some(self.check.rect)
+        } else {
+            None
+        }
+    }
+
+    fn click(&mut self, point: &Point) -> Option<M> {
+        self.check.click(point)
+    }
+
+    fn sync(&mut self, is_on: &Fn(&M) -> bool) {
+        self.check.checked = is_on(&self.check.message);
+    }
+}
+
+fn key_char(keycode: Keycode, shift: bool) -> Option<char> {
+    match keycode {
+        Keycode::Space => Some(' '),
+        Keycode::Minus => Some(if shift { '_' } else { '-' }),
+        _ => {
+            let name = keycode.name();
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_graphic() => Some(if shift {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                }),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Single line text field, focused by clicking it. Enter submits the value.
+pub struct TextInput<M> {
+    assets: Rc<Assets>,
+    value: String,
+    placeholder: String,
+    rendered: RefCell<Option<Text>>,
+    rect: Rect,
+    focused: bool,
+    hovered: bool,
+    on_submit: Box<Fn(&str) -> M>,
+}
+
+impl<M> TextInput<M> {
+    pub fn new(
+        assets: Rc<Assets>,
+        width: f32,
+        placeholder: &str,
+        on_submit: Box<Fn(&str) -> M>,
+    ) -> TextInput<M> {
+        TextInput {
+            assets,
+            value: String::new(),
+            placeholder: String::from(placeholder),
+            rendered: RefCell::new(None),
+            rect: sized_rect((width, 36.0)),
+            focused: false,
+            hovered: false,
+            on_submit,
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn set_value(&mut self, value: &str) {
+        self.value = String::from(value);
+        *self.rendered.borrow_mut() = None;
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+}
+
+impl<M> Widget<M> for TextInput<M> {
+    fn size(&self) -> (f32, f32) {
+        (self.rect.w, self.rect.h)
+    }
+
+    fn layout(&mut self, center: Point) {
+        self.rect.x = center.x;
+        self.rect.y = center.y;
+    }
+
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        if self.rendered.borrow().is_none() {
+            let shown = if self.value.is_empty() && !self.focused {
+                self.placeholder.clone()
+            } else if self.focused {
+                format!("{}|", self.value)
+            } else {
+                self.value.clone()
+            };
+            let text = Text::new(ctx, &shown, &self.assets.font)?;
+            *self.rendered.borrow_mut() = Some(text);
+        }
+
+        graphics::rectangle(ctx, DrawMode::Line, self.rect)?;
+        if let Some(ref text) = *self.rendered.borrow() {
+            draw_text_at(ctx, text, self.rect.left() + PADDING / 2.0, self.rect.y)?;
+        }
+        if self.focused {
+            draw_rect_with_outline(ctx, selected_color(), &self.rect)?;
+        } else if self.hovered {
+            draw_rect_with_outline(ctx, hover_color(), &self.rect)?;
+        }
+        Ok(())
+    }
+
+    fn hover(&mut self, point: &Point) -> Option<Rect> {
+        self.hovered = point_within(point, &self.rect);
+        if self.hovered {
+            Some(self.rect)
+        } else {
+            None
+        }
+    }
+
+    fn click(&mut self, point: &Point) -> Option<M> {
+        let focused = point_within(point, &self.rect);
+        if focused != self.focused {
+            self.focused = focused;
+            *self.rendered.borrow_mut() = None;
+        }
+        None
+    }
+
+    fn key(&mut self, keycode: Keycode, shift: bool) -> Option<M> {
+        if !self.focused {
+            return None;
+        }
+        *self.rendered.borrow_mut() = None;
+
+        match keycode {
+            Keycode::Backspace => {
+                self.value.pop();
+                None
+            }
+            Keycode::Return | Keycode::KpEnter => Some((*self.on_submit)(&self.value)),
+            Keycode::Escape => {
+                self.focused = false;
+                None
+            }
+            _ => {
+                if let Some(c) = key_char(keycode, shift) {
+                    self.value.push(c);
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Open state and selection of a `Dropdown`, kept apart from the labels.
+struct Choices<M> {
+    header: Rect,
+    messages: Vec<M>,
+    selected: usize,
+    open: bool,
+}
+
+impl<M: Clone> Choices<M> {
+    fn option_rect(&self, ix: usize) -> Rect {
+        let mut r = self.header;
+        r.y += (ix + 1) as f32 * r.h;
+        r
+    }
+
+    /// Option under the point, only while the list is unfolded.
+    fn option_at(&self, point: &Point) -> Option<usize> {
+        if !self.open {
+            return None;
+        }
+        (0..self.messages.len()).find(|&ix| point_within(point, &self.option_rect(ix)))
+    }
+
+    fn click(&mut self, point: &Point) -> Option<M> {
+        if point_within(point, &self.header) {
+            self.open = !self.open;
+            return None;
+        }
+        let picked = self.option_at(point);
+        self.open = false;
+        picked.map(|ix| {
+            self.selected = ix;
+            self.messages[ix].clone()
+        })
+    }
+
+    fn sync(&mut self, is_on: &Fn(&M) -> bool) {
+        if let Some(ix) = self.messages.iter().position(|m| is_on(m)) {
+            self.selected = ix;
+        }
+    }
+}
+
+/// Button that unfolds a list of choices below itself.
+pub struct Dropdown<M> {
+    choices: Choices<M>,
+    labels: Vec<Text>,
+    hovered: Option<Rect>,
+}
+
+impl<M: Clone> Dropdown<M> {
+    pub fn new(
+        ctx: &mut Context,
+        font: &Font,
+        size: (f32, f32),
+        options: Vec<(&str, M)>,
+        selected: usize,
+    ) -> GameResult<Dropdown<M>> {
+        let mut labels = vec![];
+        let mut messages = vec![];
+        for (label, message) in options.into_iter() {
+            labels.push(Text::new(ctx, label, font)?);
+            messages.push(message);
+        }
+        Ok(Dropdown {
+            choices: Choices {
+                header: sized_rect(size),
+                messages,
+                selected,
+                open: false,
+            },
+            labels,
+            hovered: None,
+        })
+    }
+
+    pub fn selected(&self) -> Option<&M> {
+        self.choices.messages.get(self.choices.selected)
+    }
+
+    /// Whether the choices are unfolded, they cover whatever is below.
+    pub fn is_open(&self) -> bool {
+        self.choices.open
+    }
+}
+
+impl<M: Clone> Widget<M> for Dropdown<M> {
+    fn size(&self) -> (f32, f32) {
+        (self.choices.header.w, self.choices.header.h)
+    }
+
+    fn layout(&mut self, center: Point) {
+        self.choices.header.x = center.x;
+        self.choices.header.y = center.y;
+    }
+
+    fn rect(&self) -> Rect {
+        self.choices.header
+    }
+
+    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let header = self.choices.header;
+        graphics::rectangle(ctx, DrawMode::Line, header)?;
+        if let Some(text) = self.labels.get(self.choices.selected) {
+            graphics::draw(ctx, text, center(&header), 0.0)?;
+        }
+
+        if self.choices.open {
+            for (ix, text) in self.labels.iter().enumerate() {
+                let r = self.choices.option_rect(ix);
+                graphics::set_color(ctx, Color::new(0.1, 0.1, 0.1, 1.0))?;
+                graphics::rectangle(ctx, DrawMode::Fill, r)?;
+                graphics::set_color(ctx, graphics::WHITE)?;
+                graphics::rectangle(ctx, DrawMode::Line, r)?;
+                graphics::draw(ctx, text, center(&r), 0.0)?;
+                if ix == self.choices.selected {
+                    draw_rect_with_outline(ctx, selected_color(), &r)?;
+                }
+            }
+        }
+
+        if let Some(h) = self.hovered {
+            draw_rect_with_outline(ctx, hover_color(), &h)?;
+        }
+        Ok(())
+    }
+
+    fn hover(&mut self, point: &Point) -> Option<Rect> {
+        let choices = &self.choices;
+        let hovered = match choices.option_at(point) {
+            Some(ix) => Some(choices.option_rect(ix)),
+            None if point_within(point, &choices.header) => Some(choices.header),
+            None => None,
+        };
+        self.hovered = hovered;
+        hovered
+    }
+
+    fn click(&mut self, point: &Point) -> Option<M> {
+        self.choices.click(point)
+    }
+
+    fn sync(&mut self, is_on: &Fn(&M) -> bool) {
+        self.choices.sync(is_on)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+    /// Rows of the given number of columns, every cell sized like the largest child.
+    Grid(usize),
+}
+
+/// Lays out its children in a row, a column or a grid.
+pub struct Container<M> {
+    direction: Direction,
+    spacing: f32,
+    children: Vec<Box<Widget<M>>>,
+    rect: Rect,
+}
+
+impl<M: 'static> Container<M> {
+    pub fn new(direction: Direction, spacing: f32) -> Container<M> {
+        Container {
+            direction,
+            spacing,
+            children: vec![],
+            rect: sized_rect((0.0, 0.0)),
+        }
+    }
+
+    pub fn horizontal(spacing: f32) -> Container<M> {
+        Container::new(Direction::Horizontal, spacing)
+    }
+
+    pub fn vertical(spacing: f32) -> Container<M> {
+        Container::new(Direction::Vertical, spacing)
+    }
+
+    pub fn grid(columns: usize, spacing: f32) -> Container<M> {
+        Container::new(Direction::Grid(columns.max(1)), spacing)
+    }
+
+    pub fn with<W: Widget<M> + 'static>(mut self, child: W) -> Container<M> {
+        self.push(child);
+        self
+    }
+
+    pub fn push<W: Widget<M> + 'static>(&mut self, child: W) {
+        self.children.push(Box::new(child));
+    }
+
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    fn cell(&self) -> (f32, f32) {
+        self.children.iter().fold((0.0, 0.0), |(w, h), c| {
+            let (cw, ch) = c.size();
+            (w.max(cw), h.max(ch))
+        })
+    }
+
+    fn grid_shape(&self, columns: usize) -> (usize, usize) {
+        let rows = (self.children.len() + columns - 1) / columns;
+        (columns.min(self.children.len()), rows)
+    }
+}
+
+impl<M: 'static> Widget<M> for Container<M> {
+    fn size(&self) -> (f32, f32) {
+        let gaps = |n: usize| self.spacing * (n.max(1) - 1) as f32;
+        let sizes: Vec<(f32, f32)> = self.children.iter().map(|c| c.size()).collect();
+        match self.direction {
+            Direction::Horizontal => (
+                sizes.iter().map(|s| s.0).sum::<f32>() + gaps(sizes.len()),
+                sizes.iter().fold(0.0, |h, s| s.1.max(h)),
+            ),
+            Direction::Vertical => (
+                sizes.iter().fold(0.0, |w, s| s.0.max(w)),
+                sizes.iter().map(|s| s.1).sum::<f32>() + gaps(sizes.len()),
+            ),
+            Direction::Grid(columns) => {
+                let (cw, ch) = self.cell();
+                let (cols, rows) = self.grid_shape(columns);
+                (cw * cols as f32 + gaps(cols), ch * rows as f32 + gaps(rows))
+            }
+        }
+    }
+
+    fn layout(&mut self, center: Point) {
+        let (w, h) = self.size();
+        self.rect = Rect::new(center.x, center.y, w, h);
+        let (left, top) = (center.x - w / 2.0, center.y - h / 2.0);
+        let spacing = self.spacing;
+
+        match self.direction {
+            Direction::Horizontal => {
+                let mut x = left;
+                for c in self.children.iter_mut() {
+                    let (cw, _) = c.size();
+                    c.layout(Point::new(x + cw / 2.0, center.y));
+                    x += cw + spacing;
+                }
+            }
+            Direction::Vertical => {
+                let mut y = top;
+                for c in self.children.iter_mut() {
+                    let (_, ch) = c.size();
+                    c.layout(Point::new(center.x, y + ch / 2.0));
+                    y += ch + spacing;
+                }
+            }
+            Direction::Grid(columns) => {
+                let (cw, ch) = self.cell();
+                for (ix, c) in self.children.iter_mut().enumerate() {
+                    let (col, row) = ((ix % columns) as f32, (ix / columns) as f32);
+                    c.layout(Point::new(
+                        left + col * (cw + spacing) + cw / 2.0,
+                        top + row * (ch + spacing) + ch / 2.0,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        for c in self.children.iter() {
+            c.draw(ctx)?;
+        }
+        Ok(())
+    }
+
+    fn hover(&mut self, point: &Point) -> Option<Rect> {
+        self.children
+            .iter_mut()
+            .fold(None, |found, c| c.hover(point).or(found))
+    }
+
+    fn click(&mut self, point: &Point) -> Option<M> {
+        self.children
+            .iter_mut()
+            .fold(None, |found, c| c.click(point).or(found))
+    }
+
+    fn key(&mut self, keycode: Keycode, shift: bool) -> Option<M> {
+        self.children
+            .iter_mut()
+            .fold(None, |found, c| c.key(keycode, shift).or(found))
+    }
+
+    fn sync(&mut self, is_on: &Fn(&M) -> bool) {
+        for c in self.children.iter_mut() {
+            c.sync(is_on);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check() -> Check<u8> {
+        Check {
+            rect: Rect::new(100.0, 100.0, 80.0, 30.0),
+            checked: false,
+            message: 7,
+        }
+    }
+
+    fn choices() -> Choices<&'static str> {
+        Choices {
+            header: Rect::new(100.0, 100.0, 80.0, 30.0),
+            messages: vec!["a", "b", "c"],
+            selected: 0,
+            open: false,
+        }
+    }
+
+    #[test]
+    fn checkbox_toggles_on_click() {
+        let mut check = check();
+        assert_eq!(check.click(&Point::new(100.0, 100.0)), Some(7));
+        assert!(check.checked);
+        assert_eq!(check.click(&Point::new(100.0, 100.0)), Some(7));
+        assert!(!check.checked);
+        assert_eq!(check.click(&Point::new(300.0, 100.0)), None);
+        assert!(!check.checked);
+    }
+
+    #[test]
+    fn dropdown_picks_an_unfolded_option() {
+        let mut choices = choices();
+        let second = choices.option_rect(1);
+        let second = Point::new(second.x, second.y);
+        assert_eq!(choices.click(&second), None);
+
+        assert_eq!(choices.click(&Point::new(100.0, 100.0)), None);
+        assert!(choices.open);
+        assert_eq!(choices.click(&second), Some("b"));
+        assert_eq!(choices.selected, 1);
+        assert!(!choices.open);
+    }
+
+    #[test]
+    fn dropdown_folds_on_click_elsewhere() {
+        let mut choices = choices();
+        choices.click(&Point::new(100.0, 100.0));
+        assert_eq!(choices.click(&Point::new(500.0, 500.0)), None);
+        assert!(!choices.open);
+        assert_eq!(choices.selected, 0);
+    }
+
+    #[test]
+    fn dropdown_syncs_selection() {
+        let mut choices = choices();
+        choices.sync(&|m| *m == "c");
+        assert_eq!(choices.selected, 2);
+        choices.sync(&|_| false);
+        assert_eq!(choices.selected, 2);
+    }
+}